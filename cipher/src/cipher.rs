@@ -1,32 +1,81 @@
-use crate::Error;
+use crate::field::{Bls12381, Capacity, CipherField, WIDTH};
+use crate::{Cipher, Error};
 
 #[cfg(feature = "canon")]
-use canonical_derive::Canon;
+use canonical::{Canon, CanonError, Sink, Source};
 
 use dusk_bls12_381::BlsScalar;
 use dusk_bytes::{DeserializableSlice, Error as BytesError, Serializable};
-use dusk_hades::strategies::{ScalarStrategy, Strategy};
 
 use core::ops::Mul;
 use dusk_jubjub::{JubJubAffine, JubJubScalar, GENERATOR};
 use rand_core::OsRng;
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+use subtle::ConstantTimeEq;
+#[cfg(feature = "zeroize")]
+use zeroize::{Zeroize, ZeroizeOnDrop};
 
-#[forbid(unsafe_code)]
-use sha3::{Digest, Keccak256};
-use std::convert::TryInto;
+use sha3::digest::{ExtendableOutput, Update, XofReader};
+use sha3::{Digest, Keccak256, Shake256};
 
-use std::{fmt, u64, usize};
+use std::convert::TryFrom;
+use std::fmt;
 
-const MESSAGE_CAPACITY: usize = 4;
-const CIPHER_SIZE: usize = MESSAGE_CAPACITY + 1;
+pub(crate) const MESSAGE_CAPACITY: usize = 4;
+pub(crate) const CIPHER_SIZE: usize = MESSAGE_CAPACITY + 1;
 const CIPHER_BYTES_SIZE: usize = CIPHER_SIZE * BlsScalar::SIZE;
-
-#[derive(Debug, Copy, Clone, PartialEq, Eq, Ord, PartialOrd, Default)]
-#[cfg_attr(feature = "canon", derive(Canon))]
+const DEFAULT_MAX_BLOCKS: usize = 1 << 16;
+/// Size of the little-endian length prefix of a framed message
+const LENGTH_PREFIX_BYTES: usize = 8;
+/// Message bytes packed into a scalar. One byte less than a full scalar so
+/// that any chunk is below the field modulus.
+const SCALAR_MESSAGE_BYTES: usize = BlsScalar::SIZE - 1;
 
 /// Encapsulates an encrypted data
-pub struct PoseidonCipher {
-  cipher: [BlsScalar; CIPHER_SIZE],
+///
+/// `C` is the number of message scalars absorbed per block. Since the Hades
+/// permutation has a fixed width of [`crate::WIDTH`], `C` must be between
+/// `1` and `WIDTH - 1`; it defaults to the full rate of the permutation.
+/// Other capacities are rejected at compile time:
+///
+/// ```compile_fail
+/// let cipher = cipher::PoseidonCipher::<0>::default();
+/// ```
+///
+/// `F` is the field the cipher operates over. Serialization and the
+/// message-level helpers are only provided for the default [`Bls12381`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Ord, PartialOrd)]
+pub struct PoseidonCipher<const C: usize = MESSAGE_CAPACITY, F: CipherField = Bls12381> {
+  cipher: [F::Scalar; C],
+  tag: F::Scalar,
+}
+
+impl<const C: usize, F: CipherField> Default for PoseidonCipher<C, F> {
+  fn default() -> Self {
+    Self::new([F::from_u64(0); C], F::from_u64(0))
+  }
+}
+
+#[cfg(feature = "canon")]
+impl<const C: usize> Canon for PoseidonCipher<C> {
+  fn encode(&self, sink: &mut Sink) {
+    self.cipher.iter().for_each(|c| c.encode(sink));
+    self.tag.encode(sink);
+  }
+
+  fn decode(source: &mut Source) -> Result<Self, CanonError> {
+    let mut cipher = [BlsScalar::zero(); C];
+    for c in cipher.iter_mut() {
+      *c = BlsScalar::decode(source)?;
+    }
+
+    Ok(Self::new(cipher, BlsScalar::decode(source)?))
+  }
+
+  fn encoded_len(&self) -> usize {
+    self.cipher.iter().map(Canon::encoded_len).sum::<usize>() + self.tag.encoded_len()
+  }
 }
 
 pub struct BlsScalarInfo {
@@ -51,6 +100,61 @@ impl fmt::Debug for BlsScalarInfo {
   }
 }
 
+/// Secret key derived from a VDF output that is wiped from memory when it
+/// is dropped. Dereferences to the underlying [`JubJubAffine`].
+///
+/// It is not `Clone`, so that no copy outlives the wiped one, and its
+/// `Debug` output leaves the key out.
+#[cfg(feature = "zeroize")]
+#[derive(PartialEq)]
+pub struct SecretKey(JubJubAffine);
+
+#[cfg(feature = "zeroize")]
+impl fmt::Debug for SecretKey {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "SecretKey(..)")
+  }
+}
+
+#[cfg(feature = "zeroize")]
+impl core::ops::Deref for SecretKey {
+  type Target = JubJubAffine;
+
+  fn deref(&self) -> &JubJubAffine {
+    &self.0
+  }
+}
+
+#[cfg(feature = "zeroize")]
+impl Zeroize for SecretKey {
+  fn zeroize(&mut self) {
+    zeroize_plain(&mut self.0);
+  }
+}
+
+#[cfg(feature = "zeroize")]
+impl Drop for SecretKey {
+  fn drop(&mut self) {
+    self.zeroize();
+  }
+}
+
+#[cfg(feature = "zeroize")]
+impl ZeroizeOnDrop for SecretKey {}
+
+/// Wipes the bytes of a scalar or a point, whose limbs are private to their
+/// crate. Does nothing without the `zeroize` feature.
+#[cfg(feature = "zeroize")]
+fn zeroize_plain<T: Copy>(value: &mut T) {
+  // SAFETY: `T` is `Copy`, so it owns no memory elsewhere, and the scalars
+  // and points this is used on are plain limbs for which zero bytes are valid
+  let bytes = unsafe { core::slice::from_raw_parts_mut(value as *mut T as *mut u8, core::mem::size_of::<T>()) };
+  bytes.zeroize();
+}
+
+#[cfg(not(feature = "zeroize"))]
+fn zeroize_plain<T: Copy>(_value: &mut T) {}
+
 
 impl Serializable<CIPHER_BYTES_SIZE> for PoseidonCipher {
   type Error = BytesError;
@@ -59,9 +163,9 @@ impl Serializable<CIPHER_BYTES_SIZE> for PoseidonCipher {
   fn to_bytes(&self) -> [u8; Self::SIZE] {
     let mut bytes = [0u8; Self::SIZE];
 
-    self.cipher.iter().enumerate().for_each(|(i, c)| {
+    self.cipher.iter().chain(Some(&self.tag)).enumerate().for_each(|(i, c)| {
       let n = i * BlsScalar::SIZE;
-      bytes[n..n + BlsScalar::SIZE].copy_from_slice(&c.to_bytes());        
+      bytes[n..n + BlsScalar::SIZE].copy_from_slice(&c.to_bytes());
     });
 
     bytes
@@ -78,16 +182,120 @@ impl Serializable<CIPHER_BYTES_SIZE> for PoseidonCipher {
       *scalar = BlsScalar::from_slice(&bytes[idx..len])?;
     }
 
-    Ok(Self::new(cipher))
+    let mut message = [BlsScalar::zero(); MESSAGE_CAPACITY];
+    message.copy_from_slice(&cipher[..MESSAGE_CAPACITY]);
+
+    Ok(Self::new(message, cipher[MESSAGE_CAPACITY]))
   }
 }
 
-impl PoseidonCipher {
+impl<const C: usize, F: CipherField> PoseidonCipher<C, F> {
+  /// Number of message scalars per block
+  pub const CAPACITY: usize = C;
+
   /// [`PoseidonCipher`] constructor
-  pub const fn new(cipher: [BlsScalar; CIPHER_SIZE]) -> Self {
-      Self { cipher }
+  pub const fn new(cipher: [F::Scalar; C], tag: F::Scalar) -> Self {
+      let () = Capacity::<C>::CHECK;
+      Self { cipher, tag }
+  }
+
+  /// Getter for the encrypted message scalars
+  pub const fn cipher(&self) -> &[F::Scalar; C] {
+      &self.cipher
+  }
+
+  /// Getter for the authentication tag
+  pub const fn tag(&self) -> &F::Scalar {
+      &self.tag
+  }
+
+  pub fn initial_state(
+      secret: &F::Point,
+      nonce: F::Scalar,
+  ) -> [F::Scalar; WIDTH] {
+      F::initial_state::<C>(secret, nonce)
+  }
+
+  pub fn encrypt(message: &[F::Scalar], secret: &F::Point, nonce: &F::Scalar) -> Self {
+      let (cipher, tag) = F::encrypt::<C>(message, secret, nonce);
+      Self::new(cipher, tag)
+  }
+
+  pub fn decrypt(
+      &self,
+      secret: &F::Point,
+      nonce: &F::Scalar,
+  ) -> Result<[F::Scalar; C], Error> {
+      F::decrypt(&self.cipher, &self.tag, secret, nonce)
+  }
+
+  /// Encrypts `message` and binds the cipher to the associated data `ad`,
+  /// which is authenticated but not encrypted. With an empty `ad` this is
+  /// the same as [`PoseidonCipher::encrypt`].
+  pub fn encrypt_with_ad(
+      message: &[F::Scalar],
+      secret: &F::Point,
+      nonce: &F::Scalar,
+      ad: &[F::Scalar],
+  ) -> Self {
+      let (cipher, tag) = F::encrypt_with_ad::<C>(message, secret, nonce, ad);
+      Self::new(cipher, tag)
+  }
+
+  /// Decrypts a cipher produced by [`PoseidonCipher::encrypt_with_ad`],
+  /// failing if `ad` differs from the associated data it was bound to
+  pub fn decrypt_with_ad(
+      &self,
+      secret: &F::Point,
+      nonce: &F::Scalar,
+      ad: &[F::Scalar],
+  ) -> Result<[F::Scalar; C], Error> {
+      F::decrypt_with_ad(&self.cipher, &self.tag, secret, nonce, ad)
+  }
+
+  /// Decrypts `old_cipher` with the old key and nonce and encrypts the
+  /// recovered message again under `new_secret` and `new_nonce`
+  pub fn reencrypt(
+      old_cipher: &Self,
+      old_secret: &F::Point,
+      old_nonce: &F::Scalar,
+      new_secret: &F::Point,
+      new_nonce: &F::Scalar,
+  ) -> Result<Self, Error> {
+      let message = old_cipher.decrypt(old_secret, old_nonce)?;
+
+      Ok(Self::encrypt(&message, new_secret, new_nonce))
+  }
+
+  /// Checks whether `secret` and `nonce` authenticate this cipher without
+  /// exposing the decrypted message. The tag comparison is constant-time.
+  pub fn check_key(&self, secret: &F::Point, nonce: &F::Scalar) -> bool {
+      let mut state = F::initial_state::<C>(secret, *nonce);
+
+      F::permute(&mut state);
+      state[1..=C].copy_from_slice(&self.cipher);
+      F::permute(&mut state);
+
+      let authenticated = self.tag.ct_eq(&state[1]).into();
+      F::zeroize(&mut state);
+
+      authenticated
+  }
+}
+
+impl<const C: usize, F: CipherField> Cipher<C, F> for PoseidonCipher<C, F> {
+  type Error = Error;
+
+  fn encrypt(message: &[F::Scalar], secret: &F::Point, nonce: &F::Scalar) -> Self {
+      Self::encrypt(message, secret, nonce)
   }
 
+  fn decrypt(&self, secret: &F::Point, nonce: &F::Scalar) -> Result<[F::Scalar; C], Error> {
+      Self::decrypt(self, secret, nonce)
+  }
+}
+
+impl PoseidonCipher {
   /// Maximum number of scalars allowed per message
   pub const fn capacity() -> usize {
       MESSAGE_CAPACITY
@@ -103,151 +311,254 @@ impl PoseidonCipher {
       CIPHER_BYTES_SIZE
   }
 
-  /// Getter for the cipher
-  pub const fn cipher(&self) -> &[BlsScalar; CIPHER_SIZE] {
-      &self.cipher
+  /// Default maximum number of blocks accepted for a single message
+  pub const fn default_max_blocks() -> usize {
+    DEFAULT_MAX_BLOCKS
   }
 
-  pub fn initial_state(
-      secret: &JubJubAffine,
-      nonce: BlsScalar,
-  ) -> [BlsScalar; dusk_hades::WIDTH] {
-      [
-        // Domain - Maximum plaintext length of the elements of Fq, as defined in the paper
-        BlsScalar::from_raw([0x100000000u64, 0, 0, 0]),
-        // The size of the message is constant because any absent input is replaced by zero
-        BlsScalar::from_raw([MESSAGE_CAPACITY as u64, 0, 0, 0]), 
-        secret.get_x(),
-        secret.get_y(),
-        nonce,
-      ]
-  }
-
-  pub fn encrypt(message: &[BlsScalar], secret: &JubJubAffine, nonce: &BlsScalar) -> Self {
-      let zero = BlsScalar::zero();
-      // let mut a = message[1];
-      // let mut b = BlsScalar::one();
-      let mut strategy = ScalarStrategy::new();
-      let mut cipher = [zero; CIPHER_SIZE]; // [zero; 3]
-
-      let mut state = PoseidonCipher::initial_state(secret, *nonce);
-      strategy.perm(&mut state);
-
-      (0..MESSAGE_CAPACITY).for_each(|i| {
-          state[i + 1] += if i < message.len() {
-              message[i]
-          } else {
-              BlsScalar::zero()
-          };
-          cipher[i] = state[i + 1];
-      });
-
-      strategy.perm(&mut state);
-
-      cipher[MESSAGE_CAPACITY] = state[1]; // cipher[2] = state[1]
-      PoseidonCipher::new(cipher)
+  /// Rejects ciphertexts with more than `max_blocks` blocks before any of
+  /// them is decrypted
+  pub fn check_block_count(blocks: usize, max_blocks: usize) -> Result<(), Error> {
+    if blocks > max_blocks {
+      return Err(Error::TooManyBlocks);
+    }
+
+    Ok(())
   }
 
-  pub fn decrypt(
-      &self,
-      secret: &JubJubAffine,
-      nonce: &BlsScalar,
-  ) -> Result<[BlsScalar; MESSAGE_CAPACITY], Error> {
-      let zero = BlsScalar::zero();
-      let mut strategy = ScalarStrategy::new();
-      let mut message = [zero; MESSAGE_CAPACITY];
-      let mut state = PoseidonCipher::initial_state(secret, *nonce);
+  /// Checks that `Keccak256(message)` equals `expected_digest`, catching a
+  /// message that decrypted correctly under the wrong key
+  pub fn check_digest(message: &[u8], expected_digest: &[u8]) -> Result<(), Error> {
+    let digest = Keccak256::digest(message);
 
-      strategy.perm(&mut state);
+    if digest.as_slice() != expected_digest {
+      return Err(Error::DigestMismatch);
+    }
 
-      (0..MESSAGE_CAPACITY).for_each(|i| {
-          message[i] = self.cipher[i] - state[i + 1];
-      state[i + 1] = self.cipher[i];
-      });
+    Ok(())
+  }
 
-      strategy.perm(&mut state);
+  pub fn get_secret_key(y: [u8; 258]) -> JubJubAffine {
+    PoseidonCipher::derive_secret_key(&y)
+  }
 
-      if self.cipher[MESSAGE_CAPACITY] != state[1] {
-          return Err(Error::CipherDecryptionFailed);
-      }
+  /// Same as [`PoseidonCipher::get_secret_key`] for a VDF output of any
+  /// length, as produced by discriminants other than 2048 bits
+  pub fn get_secret_key_from_slice(y: &[u8]) -> JubJubAffine {
+    PoseidonCipher::derive_secret_key(y)
+  }
 
-      Ok(message)
+  /// Same as [`PoseidonCipher::get_secret_key`], with the key wrapped so
+  /// that it is wiped from memory once dropped
+  #[cfg(feature = "zeroize")]
+  pub fn get_zeroizing_secret_key(y: [u8; 258]) -> SecretKey {
+    SecretKey(PoseidonCipher::derive_secret_key(&y))
   }
 
-  pub fn get_secret_key(y: [u8; 258]) -> JubJubAffine {
-    let y_bytes: &[u8] = &y;
-    let mut hasher = Keccak256::new();
-    
+  /// Derives the secret keys for many VDF outputs at once. The keys are
+  /// derived in parallel when the `rayon` feature is enabled, and
+  /// sequentially otherwise.
+  pub fn get_secret_keys(ys: &[Vec<u8>]) -> Vec<JubJubAffine> {
+    #[cfg(feature = "rayon")]
+    let ys = ys.par_iter();
+    #[cfg(not(feature = "rayon"))]
+    let ys = ys.iter();
+
+    ys.map(|y| PoseidonCipher::derive_secret_key(y)).collect()
+  }
+
+  /// Derives the secret key of a VDF output `y` from 64 bytes of SHAKE256
+  /// output over the raw `y` bytes, reduced into a JubJub scalar.
+  ///
+  /// Keys used to be derived from the ASCII hex text of a Keccak256 digest,
+  /// which only ever fed 16 distinct byte values into the scalar. Keys
+  /// derived by this function differ from the ones derived before, so
+  /// ciphertexts produced with the old derivation can no longer be decrypted.
+  fn derive_secret_key(y_bytes: &[u8]) -> JubJubAffine {
+    let mut wide = [0u8; 64];
+    PoseidonCipher::wide_secret(y_bytes, &mut wide);
+    let mut secret = JubJubScalar::from_bytes_wide(&wide);
+    let key = GENERATOR.to_niels().mul(&secret).into();
+
+    zeroize_plain(&mut wide);
+    zeroize_plain(&mut secret);
+    key
+  }
+
+  fn wide_secret(y_bytes: &[u8], secret: &mut [u8; 64]) {
+    let mut hasher = Shake256::default();
+
     hasher.update(y_bytes);
-    let result = hasher.finalize();
-    let y_hash = format!("{:x}", result);
-    let secret = y_hash
-        .as_bytes()
-        .try_into()
-        .expect("Slice with incorrect length");
-    let secret = JubJubScalar::from_bytes_wide(&secret);
-    GENERATOR.to_niels().mul(&secret).into()
+    hasher.finalize_xof().read(secret);
   }
 
-  pub fn convert_nonce(nonce: &[u8; 32]) -> BlsScalar {
-    BlsScalar::from_bytes(&nonce).unwrap()
+  /// Parses a nonce from the bytes of a scalar, failing if they are not
+  /// exactly [`BlsScalar::SIZE`] bytes of a canonical scalar
+  pub fn convert_nonce(nonce: &[u8]) -> Result<BlsScalar, Error> {
+    if nonce.len() != BlsScalar::SIZE {
+      return Err(Error::InvalidNonce);
+    }
+
+    BlsScalar::from_slice(nonce).map_err(|_| Error::InvalidNonce)
+  }
+
+  /// Nonce of the `index`-th block of a multi-block message, derived as
+  /// `base + index` so that no two blocks share a nonce
+  pub fn block_nonce(base: &BlsScalar, index: usize) -> BlsScalar {
+    base + BlsScalar::from(index as u64)
   }
 
   pub fn gen_nonce() -> BlsScalar {
     BlsScalar::random(&mut OsRng)
   }
 
+  /// Splits a message into scalars of [`SCALAR_MESSAGE_BYTES`] bytes each,
+  /// so that any byte string maps into the field
   pub fn convert_message_to_bls_scalar(message: &[u8]) -> Vec<BlsScalarInfo> {
-    let mut message_vecs: Vec<Vec<u8>> = message.to_vec().chunks(32).map(|s| s.into()).collect();
-    // println!("message_vecs: {:?}", message_vecs);
-
-    // let mut bls_scalars = Vec<BlsScalarInfo>(...);
-    let mut bls_scalars = Vec::new();
-  
-    for (_, message_vec) in message_vecs.iter_mut().enumerate() {
-    //   println!("message_vec.capacity {:?}", message_vec.capacity());
-      let byte_length = message_vec.capacity();
-      message_vec.resize(32, 0);
-      let temp = &*message_vec;
-      let message: [u8; 32] = temp.as_slice().try_into().unwrap();
-      bls_scalars.push(BlsScalarInfo::new(BlsScalar::from_bytes(&message).unwrap(), byte_length));
-    }
-    // println!("bls_scalars: {:?}", bls_scalars);
-    bls_scalars
+    message
+      .chunks(SCALAR_MESSAGE_BYTES)
+      .map(|chunk| {
+        let mut bytes = [0u8; BlsScalar::SIZE];
+        bytes[..chunk.len()].copy_from_slice(chunk);
+
+        let bls_scalar = BlsScalar::from_bytes(&bytes).expect("A 31-byte chunk is always in the field");
+        BlsScalarInfo::new(bls_scalar, chunk.len())
+      })
+      .collect()
   }
 
+  /// Inverse of [`PoseidonCipher::convert_message_to_bls_scalar`]; the last
+  /// scalar is zero-padded up to [`SCALAR_MESSAGE_BYTES`] bytes
   pub fn convert_bls_scalar_to_message(bls_scalars: Vec<BlsScalar>) -> Vec<u8> {
-    // let mut bls_scalars = Vec<BlsScalarInfo>(...);
-    let mut message = Vec::new();
-  
-    for (_, bls_scalar) in bls_scalars.iter().enumerate() {
-      message.extend_from_slice(&bls_scalar.to_bytes()); 
-      
+    let mut message = Vec::with_capacity(bls_scalars.len() * SCALAR_MESSAGE_BYTES);
+
+    for bls_scalar in bls_scalars.iter() {
+      message.extend_from_slice(&bls_scalar.to_bytes()[..SCALAR_MESSAGE_BYTES]);
+    }
+
+    message
+  }
+
+  /// Encrypts a message of any length into as many blocks as needed, each
+  /// under its own [`PoseidonCipher::block_nonce`]. The byte length of the
+  /// message is encrypted along with it as a little-endian `u64` prefix, so
+  /// that [`PoseidonCipher::decrypt_message`] recovers the exact bytes.
+  pub fn encrypt_message(message: &[u8], secret: &JubJubAffine, nonce: &BlsScalar) -> Vec<PoseidonCipher> {
+    let mut framed = Vec::with_capacity(LENGTH_PREFIX_BYTES + message.len());
+    framed.extend_from_slice(&(message.len() as u64).to_le_bytes());
+    framed.extend_from_slice(message);
+
+    PoseidonCipher::generates_messages(PoseidonCipher::convert_message_to_bls_scalar(&framed))
+      .iter()
+      .enumerate()
+      .map(|(i, m)| PoseidonCipher::encrypt(m, secret, &PoseidonCipher::block_nonce(nonce, i)))
+      .collect()
+  }
+
+  /// Decrypts the blocks produced by [`PoseidonCipher::encrypt_message`] and
+  /// returns the original bytes, trimmed to the length they were framed with.
+  ///
+  /// Messages of more than [`PoseidonCipher::default_max_blocks`] blocks are
+  /// rejected with [`Error::TooManyBlocks`] before any block is decrypted.
+  pub fn decrypt_message(ciphers: &[PoseidonCipher], secret: &JubJubAffine, nonce: &BlsScalar) -> Result<Vec<u8>, Error> {
+    PoseidonCipher::decrypt_message_with_max_blocks(ciphers, secret, nonce, DEFAULT_MAX_BLOCKS)
+  }
+
+  /// Same as [`PoseidonCipher::decrypt_message`], accepting at most
+  /// `max_blocks` blocks
+  pub fn decrypt_message_with_max_blocks(
+    ciphers: &[PoseidonCipher],
+    secret: &JubJubAffine,
+    nonce: &BlsScalar,
+    max_blocks: usize,
+  ) -> Result<Vec<u8>, Error> {
+    let framed =
+      PoseidonCipher::decrypt_blocks_with_max_blocks(ciphers, secret, nonce, max_blocks).map_err(|(_, e)| e)?;
+    PoseidonCipher::unframe_message(&framed)
+  }
+
+  /// Decrypts the blocks produced by [`PoseidonCipher::encrypt_message`] and
+  /// checks the message against its published Keccak256 digest with
+  /// [`PoseidonCipher::check_digest`], so that a message decrypted under the
+  /// wrong key is rejected with [`Error::DigestMismatch`]
+  pub fn timelock_decrypt_verified(
+    ciphers: &[PoseidonCipher],
+    secret: &JubJubAffine,
+    nonce: &BlsScalar,
+    expected_digest: &[u8],
+  ) -> Result<Vec<u8>, Error> {
+    let message = PoseidonCipher::decrypt_message(ciphers, secret, nonce)?;
+    PoseidonCipher::check_digest(&message, expected_digest)?;
+
+    Ok(message)
+  }
+
+  /// Recovers the message from the output of
+  /// [`PoseidonCipher::decrypt_blocks`], trimmed to the length it was framed
+  /// with by [`PoseidonCipher::encrypt_message`]
+  pub fn unframe_message(framed: &[u8]) -> Result<Vec<u8>, Error> {
+    if framed.len() < LENGTH_PREFIX_BYTES {
+      return Err(Error::CipherDecryptionFailed);
+    }
+
+    let mut length = [0u8; LENGTH_PREFIX_BYTES];
+    length.copy_from_slice(&framed[..LENGTH_PREFIX_BYTES]);
+    let message = &framed[LENGTH_PREFIX_BYTES..];
+
+    match usize::try_from(u64::from_le_bytes(length)) {
+      Ok(length) if length <= message.len() => Ok(message[..length].to_vec()),
+      _ => Err(Error::CipherDecryptionFailed),
     }
-    // println!("bls_scalars: {:?}", bls_scalars);
-    message.try_into().unwrap()
   }
 
+  /// Decrypts consecutive blocks encrypted under
+  /// [`PoseidonCipher::block_nonce`] into their padded message bytes.
+  ///
+  /// On failure, returns the zero-based index of the first block that did
+  /// not decrypt along with its error. More than
+  /// [`PoseidonCipher::default_max_blocks`] blocks are rejected up front,
+  /// reporting the first block over the limit with [`Error::TooManyBlocks`].
+  pub fn decrypt_blocks(ciphers: &[PoseidonCipher], secret: &JubJubAffine, nonce: &BlsScalar) -> Result<Vec<u8>, (usize, Error)> {
+    PoseidonCipher::decrypt_blocks_with_max_blocks(ciphers, secret, nonce, DEFAULT_MAX_BLOCKS)
+  }
+
+  /// Same as [`PoseidonCipher::decrypt_blocks`], accepting at most
+  /// `max_blocks` blocks
+  pub fn decrypt_blocks_with_max_blocks(
+    ciphers: &[PoseidonCipher],
+    secret: &JubJubAffine,
+    nonce: &BlsScalar,
+    max_blocks: usize,
+  ) -> Result<Vec<u8>, (usize, Error)> {
+    PoseidonCipher::check_block_count(ciphers.len(), max_blocks).map_err(|e| (max_blocks, e))?;
+
+    let mut scalars = Vec::with_capacity(ciphers.len() * MESSAGE_CAPACITY);
+    for (i, cipher) in ciphers.iter().enumerate() {
+      let message = cipher.decrypt(secret, &PoseidonCipher::block_nonce(nonce, i)).map_err(|e| (i, e))?;
+      scalars.extend_from_slice(&message);
+    }
+
+    Ok(PoseidonCipher::convert_bls_scalar_to_message(scalars))
+  }
+
+  /// Groups the scalars of a message into blocks of
+  /// [`PoseidonCipher::capacity`] scalars, zero-padding the last one.
+  ///
+  /// An empty message still yields a single zero block, so that an empty
+  /// plaintext is encrypted into an authenticated cipher like any other.
   pub fn generates_messages(bls_scalar_infos: Vec<BlsScalarInfo>) -> Vec<[BlsScalar; PoseidonCipher::capacity()]> {
-    let mut messages = Vec::new();
-    let mut i = 0;
-    // let mut message = ;
-    let mut index = 0;
-    messages.push([BlsScalar::zero(); PoseidonCipher::capacity()]);
-
-    for (_, bls_scalar_info) in bls_scalar_infos.iter().enumerate() {
-    //   println!("bls_scalars: {:?}", k);
-      messages[index][i] = bls_scalar_info.bls_scalar;
-
-      // message[i] = bls_scalar_info.bls_scalar;
-      i = (i + 1) % PoseidonCipher::capacity();
-
-      if i == 0 {
-        index += 1;
-        messages.push([BlsScalar::zero(); PoseidonCipher::capacity()]);
-      }
+    if bls_scalar_infos.is_empty() {
+      return vec![[BlsScalar::zero(); PoseidonCipher::capacity()]];
     }
-    messages
+
+    bls_scalar_infos
+      .chunks(PoseidonCipher::capacity())
+      .map(|chunk| {
+        let mut message = [BlsScalar::zero(); PoseidonCipher::capacity()];
+        message.iter_mut().zip(chunk).for_each(|(m, info)| *m = info.bls_scalar);
+        message
+      })
+      .collect()
   }
 }
 
@@ -256,4 +567,475 @@ impl PoseidonCipher {
 // fn demo<T, const N: usize>(v: Vec<T>) -> [T; N] {
 //     v.try_into()
 //         .unwrap_or_else(|v: Vec<T>| panic!("Expected a Vec of length {} but it was {}", N, v.len()))
-// }
\ No newline at end of file
+// }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn message() -> [BlsScalar; MESSAGE_CAPACITY] {
+    [BlsScalar::from(1u64), BlsScalar::from(2u64), BlsScalar::from(3u64), BlsScalar::from(4u64)]
+  }
+
+  #[test]
+  fn check_key_matches_tag() {
+    let secret = PoseidonCipher::get_secret_key([1u8; 258]);
+    let wrong_secret = PoseidonCipher::get_secret_key([2u8; 258]);
+    let nonce = PoseidonCipher::gen_nonce();
+
+    let cipher: PoseidonCipher = PoseidonCipher::encrypt(&message(), &secret, &nonce);
+
+    assert!(cipher.check_key(&secret, &nonce));
+    assert!(!cipher.check_key(&wrong_secret, &nonce));
+    assert!(!cipher.check_key(&secret, &(nonce + BlsScalar::one())));
+  }
+
+  #[test]
+  fn batch_secret_keys_match_single() {
+    let ys: Vec<[u8; 258]> = (0..8u8).map(|i| [i; 258]).collect();
+    let batch = PoseidonCipher::get_secret_keys(&ys.iter().map(|y| y.to_vec()).collect::<Vec<_>>());
+
+    assert_eq!(batch.len(), ys.len());
+    for (y, key) in ys.iter().zip(batch.iter()) {
+      assert_eq!(PoseidonCipher::get_secret_key(*y), *key);
+    }
+  }
+
+  #[test]
+  #[cfg(not(feature = "rayon"))]
+  fn batch_secret_keys_without_rayon() {
+    assert!(PoseidonCipher::get_secret_keys(&[]).is_empty());
+
+    let keys = PoseidonCipher::get_secret_keys(&[vec![3u8; 258], vec![4u8; 258]]);
+    assert_eq!(keys, vec![PoseidonCipher::get_secret_key([3u8; 258]), PoseidonCipher::get_secret_key([4u8; 258])]);
+  }
+
+  #[test]
+  fn generic_field_matches_concrete() {
+    let secret = PoseidonCipher::get_secret_key([5u8; 258]);
+    let nonce = PoseidonCipher::gen_nonce();
+
+    let cipher: PoseidonCipher = PoseidonCipher::encrypt(&message(), &secret, &nonce);
+    let (generic, tag) = <Bls12381 as CipherField>::encrypt::<MESSAGE_CAPACITY>(&message(), &secret, &nonce);
+
+    assert_eq!(cipher.cipher(), &generic);
+    assert_eq!(cipher.tag(), &tag);
+    assert_eq!(<Bls12381 as CipherField>::decrypt(&generic, &tag, &secret, &nonce).unwrap(), cipher.decrypt(&secret, &nonce).unwrap());
+    assert_eq!(<PoseidonCipher>::initial_state(&secret, nonce)[0], BlsScalar::from(0x100000000u64));
+  }
+
+  /// Integers modulo `2^31 - 1`, to run the cipher over a field other than
+  /// the default one
+  #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+  struct Mersenne31(u64);
+
+  const M31: u64 = (1 << 31) - 1;
+
+  impl core::ops::Add for Mersenne31 {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+      Mersenne31((self.0 + other.0) % M31)
+    }
+  }
+
+  impl core::ops::AddAssign for Mersenne31 {
+    fn add_assign(&mut self, other: Self) {
+      *self = *self + other;
+    }
+  }
+
+  impl core::ops::Sub for Mersenne31 {
+    type Output = Self;
+
+    fn sub(self, other: Self) -> Self {
+      Mersenne31((self.0 + M31 - other.0) % M31)
+    }
+  }
+
+  impl ConstantTimeEq for Mersenne31 {
+    fn ct_eq(&self, other: &Self) -> subtle::Choice {
+      self.0.ct_eq(&other.0)
+    }
+  }
+
+  #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+  struct ToyField;
+
+  impl CipherField for ToyField {
+    type Scalar = Mersenne31;
+    type Point = (u64, u64);
+
+    fn from_u64(value: u64) -> Mersenne31 {
+      Mersenne31(value % M31)
+    }
+
+    fn secret_coordinates(secret: &(u64, u64)) -> (Mersenne31, Mersenne31) {
+      (Self::from_u64(secret.0), Self::from_u64(secret.1))
+    }
+
+    fn permute(state: &mut [Mersenne31; WIDTH]) {
+      for round in 0..8 {
+        let sum = state.iter().fold(Mersenne31(0), |acc, s| acc + *s);
+        for (i, s) in state.iter_mut().enumerate() {
+          let cube = s.0 * s.0 % M31 * s.0 % M31;
+          *s = Mersenne31(cube) + sum + Self::from_u64(round * WIDTH as u64 + i as u64);
+        }
+      }
+    }
+  }
+
+  #[test]
+  fn cipher_over_another_field() {
+    let secret = (11, 13);
+    let nonce = Mersenne31(17);
+    let message = [Mersenne31(1), Mersenne31(2)];
+
+    let cipher = PoseidonCipher::<2, ToyField>::encrypt(&message, &secret, &nonce);
+    assert_eq!(cipher.decrypt(&secret, &nonce).unwrap(), message);
+    assert!(cipher.check_key(&secret, &nonce));
+    assert!(matches!(cipher.decrypt(&(11, 14), &nonce), Err(Error::CipherDecryptionFailed)));
+    assert!(matches!(cipher.decrypt(&secret, &Mersenne31(18)), Err(Error::CipherDecryptionFailed)));
+
+    let generic = <PoseidonCipher<2, ToyField> as Cipher<2, ToyField>>::encrypt(&message, &secret, &nonce);
+    assert_eq!(generic, cipher);
+  }
+
+  #[test]
+  fn reencrypt_rotates_key() {
+    let old_secret = PoseidonCipher::get_secret_key([6u8; 258]);
+    let new_secret = PoseidonCipher::get_secret_key([7u8; 258]);
+    let old_nonce = PoseidonCipher::gen_nonce();
+    let new_nonce = PoseidonCipher::gen_nonce();
+
+    let cipher: PoseidonCipher = PoseidonCipher::encrypt(&message(), &old_secret, &old_nonce);
+    let rotated = PoseidonCipher::reencrypt(&cipher, &old_secret, &old_nonce, &new_secret, &new_nonce).unwrap();
+
+    assert_eq!(rotated.decrypt(&new_secret, &new_nonce).unwrap(), message());
+    assert!(rotated.decrypt(&old_secret, &old_nonce).is_err());
+    assert!(PoseidonCipher::reencrypt(&cipher, &new_secret, &old_nonce, &new_secret, &new_nonce).is_err());
+  }
+
+  fn round_trip(plaintext: &[u8]) -> (usize, Vec<u8>) {
+    let secret = PoseidonCipher::get_secret_key([8u8; 258]);
+    let nonce = PoseidonCipher::gen_nonce();
+
+    let messages = PoseidonCipher::generates_messages(PoseidonCipher::convert_message_to_bls_scalar(plaintext));
+    let ciphers: Vec<PoseidonCipher> = messages
+      .iter()
+      .enumerate()
+      .map(|(i, m)| PoseidonCipher::encrypt(m, &secret, &PoseidonCipher::block_nonce(&nonce, i)))
+      .collect();
+
+    let mut scalars = Vec::new();
+    for (i, cipher) in ciphers.iter().enumerate() {
+      scalars.extend_from_slice(&cipher.decrypt(&secret, &PoseidonCipher::block_nonce(&nonce, i)).unwrap());
+    }
+    let mut decrypted = PoseidonCipher::convert_bls_scalar_to_message(scalars);
+    decrypted.resize(plaintext.len(), 0);
+
+    (ciphers.len(), decrypted)
+  }
+
+  #[test]
+  fn empty_plaintext_round_trip() {
+    let (blocks, decrypted) = round_trip(b"");
+
+    assert_eq!(blocks, 1);
+    assert!(decrypted.is_empty());
+  }
+
+  #[test]
+  fn full_blocks_have_no_trailing_block() {
+    let plaintext = [b'a'; SCALAR_MESSAGE_BYTES * MESSAGE_CAPACITY];
+    let (blocks, decrypted) = round_trip(&plaintext);
+
+    assert_eq!(blocks, 1);
+    assert_eq!(&decrypted[..], &plaintext[..]);
+  }
+
+  #[test]
+  fn digest_check() {
+    let secret = PoseidonCipher::get_secret_key([23u8; 258]);
+    let nonce = PoseidonCipher::gen_nonce();
+    let ciphers = PoseidonCipher::encrypt_message(b"published plaintext", &secret, &nonce);
+    let digest = Keccak256::digest(b"published plaintext");
+
+    assert_eq!(
+      PoseidonCipher::timelock_decrypt_verified(&ciphers, &secret, &nonce, &digest).unwrap(),
+      b"published plaintext"
+    );
+    assert!(matches!(
+      PoseidonCipher::timelock_decrypt_verified(&ciphers, &secret, &nonce, &Keccak256::digest(b"something else")),
+      Err(Error::DigestMismatch)
+    ));
+    assert!(matches!(
+      PoseidonCipher::timelock_decrypt_verified(&ciphers, &PoseidonCipher::get_secret_key([24u8; 258]), &nonce, &digest),
+      Err(Error::CipherDecryptionFailed)
+    ));
+  }
+
+  fn capacity_round_trip<const C: usize>(scalars: &[BlsScalar]) {
+    let secret = PoseidonCipher::get_secret_key([9u8; 258]);
+    let nonce = PoseidonCipher::gen_nonce();
+
+    let ciphers: Vec<PoseidonCipher<C>> = scalars
+      .chunks(C)
+      .enumerate()
+      .map(|(i, chunk)| PoseidonCipher::encrypt(chunk, &secret, &PoseidonCipher::block_nonce(&nonce, i)))
+      .collect();
+    assert_eq!(ciphers.len(), scalars.len().div_ceil(C));
+
+    let mut decrypted = Vec::new();
+    for (i, cipher) in ciphers.iter().enumerate() {
+      decrypted.extend_from_slice(&cipher.decrypt(&secret, &PoseidonCipher::block_nonce(&nonce, i)).unwrap());
+    }
+    decrypted.truncate(scalars.len());
+
+    assert_eq!(&decrypted[..], scalars);
+  }
+
+  #[test]
+  fn capacities_round_trip() {
+    let scalars: Vec<BlsScalar> = (1..=8u64).map(BlsScalar::from).collect();
+
+    capacity_round_trip::<2>(&scalars);
+    capacity_round_trip::<4>(&scalars);
+    capacity_round_trip::<3>(&scalars);
+    capacity_round_trip::<3>(&scalars[..5]);
+  }
+
+  #[test]
+  fn capacity_is_bound_to_the_cipher() {
+    let secret = PoseidonCipher::get_secret_key([10u8; 258]);
+    let nonce = PoseidonCipher::gen_nonce();
+
+    let narrow: PoseidonCipher<2> = PoseidonCipher::encrypt(&message()[..2], &secret, &nonce);
+    let wide: PoseidonCipher<4> = PoseidonCipher::encrypt(&message()[..2], &secret, &nonce);
+
+    assert_ne!(narrow.tag(), wide.tag());
+    assert_eq!(PoseidonCipher::<2>::CAPACITY, 2);
+  }
+
+  #[test]
+  fn block_count_limit() {
+    let max_blocks = PoseidonCipher::default_max_blocks();
+
+    assert!(PoseidonCipher::check_block_count(0, max_blocks).is_ok());
+    assert!(PoseidonCipher::check_block_count(max_blocks, max_blocks).is_ok());
+    assert!(matches!(
+      PoseidonCipher::check_block_count(max_blocks + 1, max_blocks),
+      Err(Error::TooManyBlocks)
+    ));
+  }
+
+  #[test]
+  fn decrypt_helpers_limit_blocks() {
+    let secret = PoseidonCipher::get_secret_key([25u8; 258]);
+    let nonce = PoseidonCipher::gen_nonce();
+
+    // Zero blocks never authenticate, so `TooManyBlocks` shows that nothing
+    // was decrypted
+    let oversized = vec![PoseidonCipher::default(); DEFAULT_MAX_BLOCKS + 1];
+    assert!(matches!(
+      PoseidonCipher::decrypt_message(&oversized, &secret, &nonce),
+      Err(Error::TooManyBlocks)
+    ));
+    assert!(matches!(
+      PoseidonCipher::decrypt_blocks(&oversized, &secret, &nonce),
+      Err((DEFAULT_MAX_BLOCKS, Error::TooManyBlocks))
+    ));
+    assert!(matches!(
+      PoseidonCipher::decrypt_blocks(&oversized[..1], &secret, &nonce),
+      Err((0, Error::CipherDecryptionFailed))
+    ));
+
+    let ciphers = PoseidonCipher::encrypt_message(&[b'a'; 300], &secret, &nonce);
+    assert_eq!(ciphers.len(), 3);
+    assert!(PoseidonCipher::decrypt_message_with_max_blocks(&ciphers, &secret, &nonce, 3).is_ok());
+    assert!(matches!(
+      PoseidonCipher::decrypt_message_with_max_blocks(&ciphers, &secret, &nonce, 2),
+      Err(Error::TooManyBlocks)
+    ));
+  }
+
+  fn message_round_trip(plaintext: &[u8]) -> usize {
+    let secret = PoseidonCipher::get_secret_key([11u8; 258]);
+    let nonce = PoseidonCipher::gen_nonce();
+
+    let ciphers = PoseidonCipher::encrypt_message(plaintext, &secret, &nonce);
+    assert_eq!(PoseidonCipher::decrypt_message(&ciphers, &secret, &nonce).unwrap(), plaintext);
+
+    ciphers.len()
+  }
+
+  #[test]
+  fn encrypt_message_round_trip() {
+    assert_eq!(message_round_trip(b""), 1);
+    assert_eq!(message_round_trip(b"a"), 1);
+    assert_eq!(message_round_trip(b"ends with zeros\0\0"), 1);
+    assert_eq!(message_round_trip(&[b'a'; 300]), 3);
+  }
+
+  #[test]
+  fn framed_length_is_exact() {
+    let block_bytes = SCALAR_MESSAGE_BYTES * MESSAGE_CAPACITY;
+
+    // The prefix and the message fill whole blocks
+    assert_eq!(message_round_trip(&vec![0u8; block_bytes - LENGTH_PREFIX_BYTES]), 1);
+    assert_eq!(message_round_trip(&vec![0u8; 2 * block_bytes - LENGTH_PREFIX_BYTES]), 2);
+    // One byte more spills into another block
+    assert_eq!(message_round_trip(&vec![0u8; block_bytes - LENGTH_PREFIX_BYTES + 1]), 2);
+    assert_eq!(message_round_trip(&[7u8; 5]), 1);
+  }
+
+  #[test]
+  fn truncated_message_is_rejected() {
+    let secret = PoseidonCipher::get_secret_key([20u8; 258]);
+    let nonce = PoseidonCipher::gen_nonce();
+    let ciphers = PoseidonCipher::encrypt_message(&[b'a'; 300], &secret, &nonce);
+
+    assert!(matches!(
+      PoseidonCipher::decrypt_message(&ciphers[..2], &secret, &nonce),
+      Err(Error::CipherDecryptionFailed)
+    ));
+  }
+
+  #[test]
+  fn out_of_field_bytes_round_trip() {
+    let plaintext = [0xffu8; 64];
+    let (_, decrypted) = round_trip(&plaintext);
+
+    assert_eq!(&decrypted[..], &plaintext[..]);
+    assert_eq!(message_round_trip(&plaintext), 1);
+  }
+
+  #[test]
+  fn decrypt_message_rejects_wrong_key() {
+    let secret = PoseidonCipher::get_secret_key([12u8; 258]);
+    let nonce = PoseidonCipher::gen_nonce();
+    let ciphers = PoseidonCipher::encrypt_message(b"message", &secret, &nonce);
+
+    assert!(PoseidonCipher::decrypt_message(&ciphers, &PoseidonCipher::get_secret_key([13u8; 258]), &nonce).is_err());
+    assert!(PoseidonCipher::decrypt_message(&ciphers[..0], &secret, &nonce).is_err());
+  }
+
+  #[test]
+  fn secret_key_uses_raw_digest_bytes() {
+    let mut y = [14u8; 258];
+    let first = PoseidonCipher::get_secret_key(y);
+    y[257] = 15;
+    let second = PoseidonCipher::get_secret_key(y);
+
+    assert_ne!(first, second);
+    let mut wide = [0u8; 64];
+    PoseidonCipher::wide_secret(&y, &mut wide);
+    assert!(wide.iter().any(|b| !b.is_ascii_hexdigit()));
+  }
+
+  #[test]
+  #[cfg(feature = "zeroize")]
+  fn secret_key_is_zeroized() {
+    let mut key = PoseidonCipher::get_zeroizing_secret_key([16u8; 258]);
+    let nonce = PoseidonCipher::gen_nonce();
+
+    assert_eq!(*key, PoseidonCipher::get_secret_key([16u8; 258]));
+    assert_eq!(format!("{:?}", key), "SecretKey(..)");
+    let cipher = <PoseidonCipher>::encrypt(&message(), &key, &nonce);
+    assert_eq!(cipher.decrypt(&key, &nonce).unwrap(), message());
+
+    key.zeroize();
+    assert_eq!((key.get_x(), key.get_y()), (BlsScalar::zero(), BlsScalar::zero()));
+
+    let mut secret = JubJubScalar::from(7u64);
+    zeroize_plain(&mut secret);
+    assert_eq!(secret, JubJubScalar::from(0u64));
+  }
+
+  #[test]
+  fn associated_data_is_authenticated() {
+    let secret = PoseidonCipher::get_secret_key([17u8; 258]);
+    let nonce = PoseidonCipher::gen_nonce();
+    let ad = [BlsScalar::from(100u64), BlsScalar::from(7u64)];
+
+    let cipher: PoseidonCipher = PoseidonCipher::encrypt_with_ad(&message(), &secret, &nonce, &ad);
+    assert_eq!(cipher.decrypt_with_ad(&secret, &nonce, &ad).unwrap(), message());
+
+    for wrong_ad in &[&ad[..1], &[ad[0], BlsScalar::from(8u64)][..], &[ad[0], ad[1], BlsScalar::zero()][..], &[]] {
+      assert!(matches!(cipher.decrypt_with_ad(&secret, &nonce, wrong_ad), Err(Error::CipherDecryptionFailed)));
+    }
+    assert!(cipher.decrypt(&secret, &nonce).is_err());
+  }
+
+  #[test]
+  fn empty_associated_data_matches_encrypt() {
+    let secret = PoseidonCipher::get_secret_key([18u8; 258]);
+    let nonce = PoseidonCipher::gen_nonce();
+
+    let cipher: PoseidonCipher = PoseidonCipher::encrypt(&message(), &secret, &nonce);
+
+    assert_eq!(PoseidonCipher::encrypt_with_ad(&message(), &secret, &nonce, &[]), cipher);
+    assert_eq!(cipher.decrypt_with_ad(&secret, &nonce, &[]).unwrap(), message());
+  }
+
+  fn cipher_round_trip<T: Cipher<N>, const N: usize>()
+  where
+    T::Error: fmt::Debug,
+  {
+    let secret = PoseidonCipher::get_secret_key([19u8; 258]);
+    let nonce = PoseidonCipher::gen_nonce();
+    let message: Vec<BlsScalar> = (1..=N as u64).map(BlsScalar::from).collect();
+
+    let cipher = T::encrypt(&message, &secret, &nonce);
+
+    assert_eq!(&cipher.decrypt(&secret, &nonce).unwrap()[..], &message[..]);
+    assert!(cipher.decrypt(&secret, &(nonce + BlsScalar::one())).is_err());
+  }
+
+  #[test]
+  fn poseidon_cipher_implements_cipher() {
+    cipher_round_trip::<PoseidonCipher, MESSAGE_CAPACITY>();
+    cipher_round_trip::<PoseidonCipher<2>, 2>();
+  }
+
+  #[test]
+  fn decrypt_blocks_reports_failing_block() {
+    let secret = PoseidonCipher::get_secret_key([21u8; 258]);
+    let nonce = PoseidonCipher::gen_nonce();
+    let mut ciphers = PoseidonCipher::encrypt_message(&[b'a'; 500], &secret, &nonce);
+    assert_eq!(ciphers.len(), 5);
+
+    assert!(PoseidonCipher::decrypt_blocks(&ciphers, &secret, &nonce).is_ok());
+
+    ciphers[2] = PoseidonCipher::new(*ciphers[2].cipher(), ciphers[2].tag() + BlsScalar::one());
+    assert!(matches!(
+      PoseidonCipher::decrypt_blocks(&ciphers, &secret, &nonce),
+      Err((2, Error::CipherDecryptionFailed))
+    ));
+  }
+
+  #[test]
+  fn convert_nonce_rejects_bad_bytes() {
+    let nonce = PoseidonCipher::gen_nonce();
+    let bytes = nonce.to_bytes();
+
+    assert_eq!(PoseidonCipher::convert_nonce(&bytes).unwrap(), nonce);
+    assert!(matches!(PoseidonCipher::convert_nonce(&bytes[1..]), Err(Error::InvalidNonce)));
+    assert!(matches!(PoseidonCipher::convert_nonce(&[bytes.to_vec(), vec![0]].concat()), Err(Error::InvalidNonce)));
+    assert!(matches!(PoseidonCipher::convert_nonce(&[0xff; 32]), Err(Error::InvalidNonce)));
+  }
+
+  #[test]
+  fn identical_blocks_get_distinct_nonces() {
+    let secret = PoseidonCipher::get_secret_key([22u8; 258]);
+    let nonce = PoseidonCipher::gen_nonce();
+    // The second and third blocks hold the same bytes once the length prefix
+    // has filled the start of the first one
+    let plaintext = [b'a'; 3 * SCALAR_MESSAGE_BYTES * MESSAGE_CAPACITY - LENGTH_PREFIX_BYTES];
+
+    let counter = PoseidonCipher::encrypt_message(&plaintext, &secret, &nonce);
+    assert_eq!(counter.len(), 3);
+    assert_ne!(counter[1].cipher(), counter[2].cipher());
+    assert_eq!(PoseidonCipher::decrypt_message(&counter, &secret, &nonce).unwrap(), &plaintext[..]);
+  }
+}
@@ -21,6 +21,12 @@ pub enum Error {
     TreeIterFailed,
     /// Decryption failed for the provided secret+nonce
     CipherDecryptionFailed,
+    /// The ciphertext has more blocks than the configured limit
+    TooManyBlocks,
+    /// The decrypted message does not match the expected digest
+    DigestMismatch,
+    /// The nonce is not the encoding of a scalar
+    InvalidNonce,
 }
 
 impl Display for Error {
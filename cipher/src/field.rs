@@ -0,0 +1,196 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright (c) DUSK NETWORK. All rights reserved.
+
+use crate::cipher::MESSAGE_CAPACITY;
+use crate::Error;
+
+use core::ops::{Add, AddAssign, Sub};
+use dusk_bls12_381::BlsScalar;
+use dusk_hades::strategies::{ScalarStrategy, Strategy};
+use dusk_jubjub::JubJubAffine;
+use subtle::ConstantTimeEq;
+#[cfg(feature = "zeroize")]
+use zeroize::Zeroize;
+
+/// Width of the permutation state used by the cipher; a cipher absorbs at
+/// most `WIDTH - 1` message scalars per block
+pub const WIDTH: usize = MESSAGE_CAPACITY + 1;
+
+/// Capacity `C` of a cipher, checked at compile time
+pub(crate) struct Capacity<const C: usize>;
+
+impl<const C: usize> Capacity<C> {
+    /// Fails to compile, wherever it is used, unless `C` is between `1` and
+    /// `WIDTH - 1`
+    pub(crate) const CHECK: () = assert!(C > 0 && C < WIDTH, "cipher capacity must be between 1 and WIDTH - 1");
+}
+
+/// Scalar field and curve a [`crate::PoseidonCipher`] operates over
+///
+/// Implementors only provide the field arithmetic, the coordinates of the
+/// shared secret and a Poseidon permutation of width [`WIDTH`]; the
+/// encryption scheme itself is provided by this trait.
+pub trait CipherField {
+    /// Element of the scalar field
+    type Scalar: Copy + ConstantTimeEq + Add<Output = Self::Scalar> + AddAssign + Sub<Output = Self::Scalar>;
+    /// Point of the curve used as shared secret
+    type Point;
+
+    /// Maps an integer into the scalar field
+    fn from_u64(value: u64) -> Self::Scalar;
+
+    /// Affine coordinates of the shared secret
+    fn secret_coordinates(secret: &Self::Point) -> (Self::Scalar, Self::Scalar);
+
+    /// Poseidon permutation over the whole state
+    fn permute(state: &mut [Self::Scalar; WIDTH]);
+
+    /// Wipes scalars derived from the secret before they go out of scope.
+    /// Does nothing unless the implementor overrides it.
+    fn zeroize(_scalars: &mut [Self::Scalar]) {}
+
+    /// Initial permutation state for a cipher absorbing `C` scalars per
+    /// block. Fails to compile unless `C` is between `1` and `WIDTH - 1`.
+    fn initial_state<const C: usize>(secret: &Self::Point, nonce: Self::Scalar) -> [Self::Scalar; WIDTH] {
+        let () = Capacity::<C>::CHECK;
+
+        let (x, y) = Self::secret_coordinates(secret);
+        [
+            // Domain - Maximum plaintext length of the elements of Fq, as defined in the paper
+            Self::from_u64(0x100000000u64),
+            // The size of the message is constant because any absent input is replaced by zero
+            Self::from_u64(C as u64),
+            x,
+            y,
+            nonce,
+        ]
+    }
+
+    /// Encrypts up to `C` scalars of `message`, returning the encrypted
+    /// scalars and the authentication tag
+    fn encrypt<const C: usize>(
+        message: &[Self::Scalar],
+        secret: &Self::Point,
+        nonce: &Self::Scalar,
+    ) -> ([Self::Scalar; C], Self::Scalar) {
+        Self::encrypt_with_ad::<C>(message, secret, nonce, &[])
+    }
+
+    /// Same as [`CipherField::encrypt`], additionally authenticating the
+    /// associated data `ad` without encrypting it
+    fn encrypt_with_ad<const C: usize>(
+        message: &[Self::Scalar],
+        secret: &Self::Point,
+        nonce: &Self::Scalar,
+        ad: &[Self::Scalar],
+    ) -> ([Self::Scalar; C], Self::Scalar) {
+        let zero = Self::from_u64(0);
+        let mut cipher = [zero; C];
+
+        let mut state = Self::initial_state::<C>(secret, *nonce);
+        absorb_associated_data::<Self, C>(&mut state, ad);
+        Self::permute(&mut state);
+
+        (0..C).for_each(|i| {
+            state[i + 1] += if i < message.len() { message[i] } else { zero };
+            cipher[i] = state[i + 1];
+        });
+
+        Self::permute(&mut state);
+
+        let tag = state[1];
+        Self::zeroize(&mut state);
+
+        (cipher, tag)
+    }
+
+    /// Decrypts `cipher`, failing if `tag` does not authenticate it
+    fn decrypt<const C: usize>(
+        cipher: &[Self::Scalar; C],
+        tag: &Self::Scalar,
+        secret: &Self::Point,
+        nonce: &Self::Scalar,
+    ) -> Result<[Self::Scalar; C], Error> {
+        Self::decrypt_with_ad(cipher, tag, secret, nonce, &[])
+    }
+
+    /// Decrypts `cipher`, failing if `tag` does not authenticate both the
+    /// cipher and the associated data `ad`
+    fn decrypt_with_ad<const C: usize>(
+        cipher: &[Self::Scalar; C],
+        tag: &Self::Scalar,
+        secret: &Self::Point,
+        nonce: &Self::Scalar,
+        ad: &[Self::Scalar],
+    ) -> Result<[Self::Scalar; C], Error> {
+        let mut message = [Self::from_u64(0); C];
+        let mut state = Self::initial_state::<C>(secret, *nonce);
+
+        absorb_associated_data::<Self, C>(&mut state, ad);
+        Self::permute(&mut state);
+
+        (0..C).for_each(|i| {
+            message[i] = cipher[i] - state[i + 1];
+            state[i + 1] = cipher[i];
+        });
+
+        Self::permute(&mut state);
+
+        let authenticated = bool::from(tag.ct_eq(&state[1]));
+        Self::zeroize(&mut state);
+
+        if !authenticated {
+            Self::zeroize(&mut message);
+            return Err(Error::CipherDecryptionFailed);
+        }
+
+        Ok(message)
+    }
+}
+
+/// Absorbs the associated data into the state, `C` scalars per permutation.
+/// The length of the data is added to the domain so that trailing zeros are
+/// not ignored; empty data leaves the state untouched.
+fn absorb_associated_data<F: CipherField + ?Sized, const C: usize>(
+    state: &mut [F::Scalar; WIDTH],
+    ad: &[F::Scalar],
+) {
+    if ad.is_empty() {
+        return;
+    }
+
+    state[0] += F::from_u64(ad.len() as u64);
+    ad.chunks(C).for_each(|chunk| {
+        F::permute(state);
+        state[1..].iter_mut().zip(chunk).for_each(|(s, a)| *s += *a);
+    });
+}
+
+/// BLS12-381 scalar field with JubJub secrets, the default cipher field
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Bls12381;
+
+impl CipherField for Bls12381 {
+    type Scalar = BlsScalar;
+    type Point = JubJubAffine;
+
+    fn from_u64(value: u64) -> BlsScalar {
+        BlsScalar::from_raw([value, 0, 0, 0])
+    }
+
+    fn secret_coordinates(secret: &JubJubAffine) -> (BlsScalar, BlsScalar) {
+        (secret.get_x(), secret.get_y())
+    }
+
+    fn permute(state: &mut [BlsScalar; WIDTH]) {
+        ScalarStrategy::new().perm(state);
+    }
+
+    #[cfg(feature = "zeroize")]
+    fn zeroize(scalars: &mut [BlsScalar]) {
+        scalars.iter_mut().for_each(|s| s.0.zeroize());
+    }
+}
@@ -2,17 +2,27 @@
 
 mod cipher;
 mod error;
+mod field;
+#[cfg(feature = "wasm")]
+pub mod wasm;
 
 pub use cipher::PoseidonCipher;
+#[cfg(feature = "zeroize")]
+pub use cipher::SecretKey;
+pub use field::{Bls12381, CipherField, WIDTH};
 
 pub use error::Error;
 
-pub trait Cipher {
-    //     fn encrypt(message: &[BlsScalar], secret: &JubJubAffine, nonce: &BlsScalar) -> Self;
+/// Authenticated encryption of `N` message scalars of the field `F` per
+/// cipher, implemented by every cipher backend
+pub trait Cipher<const N: usize, F: CipherField = Bls12381>: Sized {
+    /// Error returned when a cipher fails to decrypt
+    type Error;
 
-    //     fn decrypt(
-    //         &self,
-    //         secret: &JubJubAffine,
-    //         nonce: &BlsScalar,
-    //     ) -> Result<[BlsScalar; MESSAGE_CAPACITY], Error>;
+    /// Encrypts up to `N` scalars of `message`
+    fn encrypt(message: &[F::Scalar], secret: &F::Point, nonce: &F::Scalar) -> Self;
+
+    /// Decrypts the cipher, failing if it was not encrypted under `secret`
+    /// and `nonce`
+    fn decrypt(&self, secret: &F::Point, nonce: &F::Scalar) -> Result<[F::Scalar; N], Self::Error>;
 }
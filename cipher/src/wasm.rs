@@ -0,0 +1,161 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright (c) DUSK NETWORK. All rights reserved.
+
+//! `wasm-bindgen` entry points, exchanging the same JSON payloads as
+//! `vdf-cli`
+
+use crate::{Error, PoseidonCipher};
+
+use dusk_bytes::{DeserializableSlice, Serializable};
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+
+/// Version of the payloads, matching the one written by `vdf-cli`
+const SCHEMA_VERSION: u32 = 1;
+
+fn schema_version() -> u32 {
+    SCHEMA_VERSION
+}
+
+/// Output of [`encrypt_message`], accepted back by [`decrypt_message`]
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct EncryptOutput {
+    /// Payloads written before the schema was versioned are taken to be of
+    /// the current version
+    #[serde(default = "schema_version")]
+    version: u32,
+    #[serde(default)]
+    message_length: usize,
+    nonce: String,
+    cipher_text: Vec<String>,
+}
+
+/// Output of [`decrypt_message`]
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct DecryptOutput {
+    version: u32,
+    original_text: String,
+}
+
+fn cipher_error(e: Error) -> JsError {
+    JsError::new(&e.to_string())
+}
+
+/// Encrypts `message` under the key derived from the VDF output `y_bytes`.
+///
+/// `nonce` holds the bytes of a scalar; when it is empty, a fresh nonce is
+/// drawn from the browser's random number generator.
+#[wasm_bindgen]
+pub fn encrypt_message(message: &[u8], y_bytes: &[u8], nonce: &[u8]) -> Result<JsValue, JsError> {
+    let nonce = if nonce.is_empty() {
+        PoseidonCipher::gen_nonce()
+    } else {
+        PoseidonCipher::convert_nonce(nonce).map_err(cipher_error)?
+    };
+    let secret = PoseidonCipher::get_secret_key_from_slice(y_bytes);
+
+    let output = EncryptOutput {
+        version: SCHEMA_VERSION,
+        message_length: message.len(),
+        nonce: hex::encode(nonce.to_bytes()),
+        cipher_text: PoseidonCipher::encrypt_message(message, &secret, &nonce)
+            .iter()
+            .map(|cipher| hex::encode(cipher.to_bytes()))
+            .collect(),
+    };
+    Ok(serde_wasm_bindgen::to_value(&output)?)
+}
+
+/// Decrypts the output of [`encrypt_message`] with the key derived from the
+/// VDF output `y_bytes`.
+#[wasm_bindgen]
+pub fn decrypt_message(encrypted: JsValue, y_bytes: &[u8]) -> Result<JsValue, JsError> {
+    let encrypted: EncryptOutput = serde_wasm_bindgen::from_value(encrypted)?;
+    if encrypted.version > SCHEMA_VERSION {
+        return Err(JsError::new(&format!(
+            "version {} is newer than the supported version {}",
+            encrypted.version, SCHEMA_VERSION
+        )));
+    }
+    PoseidonCipher::check_block_count(encrypted.cipher_text.len(), PoseidonCipher::default_max_blocks())
+        .map_err(cipher_error)?;
+
+    let nonce = PoseidonCipher::convert_nonce(&hex::decode(&encrypted.nonce)?).map_err(cipher_error)?;
+    let ciphers = encrypted
+        .cipher_text
+        .iter()
+        .map(|cipher| {
+            PoseidonCipher::from_slice(&hex::decode(cipher)?)
+                .map_err(|_| JsError::new("cipher text is not a serialized cipher"))
+        })
+        .collect::<Result<Vec<_>, JsError>>()?;
+
+    let secret = PoseidonCipher::get_secret_key_from_slice(y_bytes);
+    let message = PoseidonCipher::decrypt_message(&ciphers, &secret, &nonce).map_err(cipher_error)?;
+
+    let output = DecryptOutput {
+        version: SCHEMA_VERSION,
+        original_text: String::from_utf8(message)?,
+    };
+    Ok(serde_wasm_bindgen::to_value(&output)?)
+}
+
+#[cfg(all(test, target_arch = "wasm32"))]
+mod tests {
+    use super::*;
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    #[wasm_bindgen_test]
+    fn round_trip() {
+        let y = [7u8; 258];
+        let encrypted = encrypt_message(b"hello from wasm", &y, &[]).unwrap();
+        let decrypted = decrypt_message(encrypted, &y).unwrap();
+
+        let output: DecryptOutput = serde_wasm_bindgen::from_value(decrypted).unwrap();
+        assert_eq!(output.original_text, "hello from wasm");
+    }
+
+    #[wasm_bindgen_test]
+    fn bad_input_is_an_error() {
+        let y = [7u8; 258];
+        assert!(encrypt_message(b"hello", &y, &[0xff; 32]).is_err());
+
+        let encrypted = encrypt_message(b"hello", &y, &[]).unwrap();
+        assert!(decrypt_message(encrypted, &[8u8; 258]).is_err());
+        assert!(decrypt_message(JsValue::from_str("not a payload"), &y).is_err());
+    }
+
+    #[wasm_bindgen_test]
+    fn payload_versions() {
+        #[derive(Serialize)]
+        #[serde(rename_all = "camelCase")]
+        struct Unversioned {
+            nonce: String,
+            cipher_text: Vec<String>,
+        }
+
+        let y = [7u8; 258];
+        let encrypted: EncryptOutput =
+            serde_wasm_bindgen::from_value(encrypt_message(b"hello", &y, &[]).unwrap()).unwrap();
+
+        let unversioned = Unversioned {
+            nonce: encrypted.nonce.clone(),
+            cipher_text: encrypted.cipher_text.clone(),
+        };
+        let parsed: EncryptOutput =
+            serde_wasm_bindgen::from_value(serde_wasm_bindgen::to_value(&unversioned).unwrap()).unwrap();
+        assert_eq!(parsed.version, SCHEMA_VERSION);
+        assert!(decrypt_message(serde_wasm_bindgen::to_value(&unversioned).unwrap(), &y).is_ok());
+
+        let future = EncryptOutput {
+            version: SCHEMA_VERSION + 1,
+            ..encrypted
+        };
+        assert!(decrypt_message(serde_wasm_bindgen::to_value(&future).unwrap(), &y).is_err());
+    }
+}
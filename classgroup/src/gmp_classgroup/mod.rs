@@ -416,6 +416,24 @@ impl ClassGroup for GmpClassGroup {
         Self::from_ab_discriminant(a, b, discriminant)
     }
 
+    fn try_from_bytes(bytearray: &[u8], discriminant: Self::BigNum) -> Option<Self> {
+        let len = (ffi::size_in_bits(&discriminant) + 16) >> 4;
+        if bytearray.len() != 2 * len {
+            return None;
+        }
+        let a = ffi::import_obj(&bytearray[..len]);
+        let b = ffi::import_obj(&bytearray[len..]);
+        if a <= Mpz::zero() {
+            return None;
+        }
+        let mut four_a: Mpz = 4u64.into();
+        four_a *= &a;
+        if !((&b * &b - &discriminant) % &four_a).is_zero() {
+            return None;
+        }
+        Some(Self::from_ab_discriminant(a, b, discriminant))
+    }
+
     fn from_ab_discriminant(a: Self::BigNum, b: Self::BigNum, discriminant: Self::BigNum) -> Self {
         let mut four_a: Self::BigNum = 4u64.into();
         four_a *= &a;
@@ -12,12 +12,16 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 #![deny(unsafe_code)]
+#![cfg_attr(not(feature = "std"), no_std)]
+use core::ops::{Mul, MulAssign, Rem, ShlAssign};
 use num_traits::{One, Zero};
-use std::ops::{Mul, MulAssign, Rem, ShlAssign};
 
+#[cfg(feature = "std")]
 pub mod gmp;
 
+#[cfg(feature = "std")]
 pub mod gmp_classgroup;
+#[cfg(feature = "std")]
 pub use self::gmp_classgroup::{
     do_compute,
     ffi::{export_obj, import_obj},
@@ -27,21 +31,21 @@ pub trait BigNum:
     + One
     + Clone
     + PartialOrd
-    + std::fmt::Debug
+    + core::fmt::Debug
     + Rem
     + ShlAssign<usize>
     + for<'a> MulAssign<&'a Self>
-    + std::ops::Sub<u64, Output = Self>
-    + std::ops::Add<u64, Output = Self>
-    + std::convert::From<u64>
-    + for<'a> std::convert::From<&'a [u8]>
-    + std::ops::Shl<usize, Output = Self>
-    + std::ops::Shr<usize, Output = Self>
-    + std::ops::Neg<Output = Self>
-    + std::str::FromStr
-    + for<'a> std::ops::Div<&'a Self, Output = Self>
+    + core::ops::Sub<u64, Output = Self>
+    + core::ops::Add<u64, Output = Self>
+    + core::convert::From<u64>
+    + for<'a> core::convert::From<&'a [u8]>
+    + core::ops::Shl<usize, Output = Self>
+    + core::ops::Shr<usize, Output = Self>
+    + core::ops::Neg<Output = Self>
+    + core::str::FromStr
+    + for<'a> core::ops::Div<&'a Self, Output = Self>
     + Eq
-    + std::hash::Hash
+    + core::hash::Hash
 {
     fn probab_prime(&self, iterations: u32) -> bool;
     fn setbit(&mut self, offset: usize);
@@ -54,7 +58,7 @@ pub trait BigNumExt: BigNum {
 }
 
 pub trait ClassGroup:
-    Sized + Clone + for<'a> MulAssign<&'a Self> + for<'a> Mul<&'a Self> + PartialEq + std::fmt::Debug
+    Sized + Clone + for<'a> MulAssign<&'a Self> + for<'a> Mul<&'a Self> + PartialEq + core::fmt::Debug
 {
     type BigNum: BigNum;
 
@@ -67,6 +71,16 @@ pub trait ClassGroup:
     /// concatenated together.
     fn from_bytes(bytearray: &[u8], discriminant: Self::BigNum) -> Self;
 
+    /// Same as `from_bytes`, but returns `None` if the byte array does not
+    /// encode an element of the class group for `discriminant`.
+    ///
+    /// The default implementation does not check anything.  Implementations
+    /// are suggested to override it, as elements that do not match the
+    /// discriminant give unspecified results.
+    fn try_from_bytes(bytearray: &[u8], discriminant: Self::BigNum) -> Option<Self> {
+        Some(Self::from_bytes(bytearray, discriminant))
+    }
+
     /// Computes the identity element of `Self` for a given discriminant.
     ///
     /// If the discriminant is not valid, the result is unspecified.
@@ -87,7 +101,7 @@ pub trait ClassGroup:
     /// is not enough space in the buffer.
     ///
     /// The data must be serialized in twos-complement, big-endian format.
-    fn serialize(&self, buf: &mut [u8]) -> std::result::Result<(), usize>;
+    fn serialize(&self, buf: &mut [u8]) -> core::result::Result<(), usize>;
 
     /// Deserializes a bignum from raw bytes.  The bytes **must** be interpreted
     /// as a big-endian unsigned integer.
@@ -168,7 +182,7 @@ pub trait ClassGroup:
     fn deserialize(buf: &[u8], discriminant: Self::BigNum) -> Self;
 }
 
-#[cfg(test)]
+#[cfg(all(test, feature = "std"))]
 mod test {
 
     use std::{
@@ -189,6 +203,19 @@ mod test {
         [fst, snd, thd]
     }
 
+    #[test]
+    fn try_from_bytes_checks_discriminant() {
+        let discriminant: Mpz = -Mpz::from(23u64);
+        let x = GmpClassGroup::generator_for_discriminant(discriminant.clone());
+        let mut buf = [0; 2];
+        x.serialize(&mut buf).unwrap();
+
+        assert_eq!(GmpClassGroup::try_from_bytes(&buf, discriminant.clone()), Some(x));
+        assert!(GmpClassGroup::try_from_bytes(&buf, -Mpz::from(27u64)).is_none());
+        assert!(GmpClassGroup::try_from_bytes(&[0, 1], discriminant.clone()).is_none());
+        assert!(GmpClassGroup::try_from_bytes(&buf[..1], discriminant).is_none());
+    }
+
     #[test]
     fn multiplication_is_correct() {
         let manifest_path =
@@ -0,0 +1,10 @@
+#!/bin/sh --
+# Builds and tests the workspace with and without the default features of
+# the vdf crate.  Without `std`, vdf is `#![no_std]` and only verifies proofs,
+# so it is also built for a target that has no `std` at all.
+set -e
+
+cargo test --workspace
+cargo test -p vdf --no-default-features
+cargo build -p vdf --no-default-features --target thumbv7em-none-eabi
+cargo test -p vdf --features rayon,serde,blake3,ffi
@@ -24,7 +24,7 @@ use cipher::PoseidonCipher;
 use dusk_bytes::Serializable;
 use std::convert::TryInto;
 use std::{cell::RefCell, fs::File, io::Read, rc::Rc, u64};
-use vdf::{InvalidProof, PietrzakVDFParams, VDFParams, WesolowskiVDFParams, VDF};
+use vdf::{estimate_eval_duration, InvalidProof, PietrzakVDFParams, VDFParams, WesolowskiVDFParams, VDF};
 
 macro_rules! gen_validator {
     ($name:ident : $type:ty) => {
@@ -37,10 +37,20 @@ macro_rules! gen_validator {
     };
 }
 
-gen_validator!(is_u16_ok: u16);
 gen_validator!(is_u64_ok: u64);
 gen_validator!(is_hex_ok, hex::decode);
 
+/// Smallest discriminant length in bits accepted by the CLI
+const MIN_LENGTH: u16 = 1024;
+
+fn is_length_ok(obj: String) -> Result<(), String> {
+    match obj.parse::<u16>() {
+        Ok(length) if length >= MIN_LENGTH => Ok(()),
+        Ok(length) => Err(format!("Discriminant length must be at least {} bits, got {}", MIN_LENGTH, length)),
+        Err(e) => Err(format!("{}", e)),
+    }
+}
+
 fn check_iterations(is_pietrzak: bool, matches: &clap::ArgMatches<'_>) -> u64 {
     let iterations = value_t!(matches, "NUM_ITERATIONS", u64).unwrap();
     if is_pietrzak && (iterations & 1 != 0 || iterations < 66) {
@@ -58,52 +68,250 @@ fn check_iterations(is_pietrzak: bool, matches: &clap::ArgMatches<'_>) -> u64 {
 use serde::{Deserialize, Serialize};
 use serde_json::{Map, Value};
 
-#[derive(Serialize, Deserialize, Debug)]
-struct Stompesi {
-  #[serde(default)] pub message_length: usize,
+/// Version of the JSON schema read and written by the CLI
+const SCHEMA_VERSION: u32 = 1;
+
+fn schema_version() -> u32 {
+  SCHEMA_VERSION
+}
+
+/// JSON payload of the CLI. Field names are camelCase; the snake_case names
+/// used before the schema was versioned are still accepted as aliases.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+struct EncryptedInfo {
+  #[serde(default = "schema_version")] pub version: u32,
+  #[serde(default, alias = "message_length")] pub message_length: usize,
   #[serde(default)] pub nonce: String,
-  #[serde(default)] pub original_text: String,
-  #[serde(default)] pub cipher_text: Vec<String>,
+  #[serde(default, alias = "original_text")] pub original_text: String,
+  #[serde(default, alias = "cipher_text")] pub cipher_text: Vec<String>,
+  #[serde(default)] pub proof: String,
   pub x: String,
   pub t: u64,
 }
 
+/// A payload that lacks a field needed by the action, or whose hex fields do
+/// not decode
+#[derive(Debug, PartialEq)]
+enum PayloadError {
+  MissingField { action: String, field: &'static str },
+  InvalidHex { field: String, error: hex::FromHexError },
+  InvalidField { field: &'static str, reason: String },
+  InvalidCipher { index: usize },
+}
 
-fn main() {
-  let validate_proof_type = |x| {
-      if x == "pietrzak" || x == "wesolowski" {
-          Ok(())
-      } else {
-          Err("Invalid proof type".to_owned())
+impl std::fmt::Display for PayloadError {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      PayloadError::MissingField { action, field } => write!(f, "{} requires field `{}`", action, field),
+      PayloadError::InvalidHex { field, error } => write!(f, "Invalid hex in {}: {}", field, error),
+      PayloadError::InvalidField { field, reason } => write!(f, "Invalid {}: {}", field, reason),
+      PayloadError::InvalidCipher { index } => write!(f, "cipherText[{}] is not a serialized cipher", index),
+    }
+  }
+}
+
+/// Hex fields of an `EncryptedInfo`, decoded after validation. Fields the
+/// action does not use are left empty.
+#[derive(Debug, Default, PartialEq)]
+struct DecodedInfo {
+  pub x: Vec<u8>,
+  pub nonce: Vec<u8>,
+  pub ciphers: Vec<PoseidonCipher>,
+  pub proof: Vec<u8>,
+}
+
+impl EncryptedInfo {
+  /// Checks that the payload is not from a newer schema, that the fields
+  /// `action` needs are present and non-empty, that `t` is a difficulty `vdf`
+  /// accepts, and decodes the hex fields
+  fn validate(&self, action: &str, vdf: &dyn VDF) -> Result<DecodedInfo, PayloadError> {
+    if self.version > SCHEMA_VERSION {
+      return Err(PayloadError::InvalidField {
+        field: "version",
+        reason: format!("{} is newer than the supported version {}", self.version, SCHEMA_VERSION),
+      });
+    }
+    vdf
+      .check_difficulty(self.t)
+      .map_err(|e| PayloadError::InvalidField { field: "t", reason: e.to_string() })?;
+
+    let decode = |field: &'static str, value: &str| {
+      if value.is_empty() {
+        return Err(PayloadError::MissingField { action: action.to_owned(), field });
       }
-  };
+      hex::decode(value).map_err(|error| PayloadError::InvalidHex { field: field.to_owned(), error })
+    };
+
+    let mut decoded = DecodedInfo { x: decode("x", &self.x)?, ..DecodedInfo::default() };
+    match action {
+      "decrypt" => {
+        decoded.nonce = decode("nonce", &self.nonce)?;
+        if self.cipher_text.is_empty() {
+          return Err(PayloadError::MissingField { action: action.to_owned(), field: "cipherText" });
+        }
+        for (index, cipher) in self.cipher_text.iter().enumerate() {
+          let bytes = hex::decode(cipher)
+            .map_err(|error| PayloadError::InvalidHex { field: format!("cipherText[{}]", index), error })?;
+          let cipher = bytes
+            .try_into()
+            .ok()
+            .and_then(|bytes| PoseidonCipher::from_bytes(&bytes).ok())
+            .ok_or(PayloadError::InvalidCipher { index })?;
+          decoded.ciphers.push(cipher);
+        }
+      }
+      "verify" => decoded.proof = decode("proof", &self.proof)?,
+      _ => {}
+    }
 
+    Ok(decoded)
+  }
+}
+
+/// Output of the `encrypt` action. Keys are emitted in field order.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct EncryptOutput<'a> {
+  pub version: u32,
+  pub message_length: usize,
+  pub nonce: String,
+  pub x: &'a str,
+  pub t: u64,
+  pub cipher_text: Vec<String>,
+}
+
+/// Output of the `decrypt` action
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct DecryptOutput<'a> {
+  pub version: u32,
+  pub original_text: &'a str,
+}
+
+/// Output of the `verify` action
+#[derive(Serialize, Debug)]
+struct VerifyOutput {
+  pub valid: bool,
+}
+
+/// Output of the `estimate` subcommand
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct EstimateOutput {
+  pub t: u64,
+  pub length: u16,
+  pub seconds: f64,
+}
+
+fn to_json<T: Serialize>(value: &T, pretty: bool) -> String {
+  if pretty {
+    serde_json::to_string_pretty(value)
+  } else {
+    serde_json::to_string(value)
+  }
+  .expect("CLI output is always serializable")
+}
+
+/// Reads the Json data from the file at `path`, or from stdin if `path` is `-`
+fn read_input(path: &str) -> std::io::Result<String> {
+  let mut data = String::new();
+  if path == "-" {
+    std::io::stdin().read_to_string(&mut data)?;
+  } else {
+    File::open(path)?.read_to_string(&mut data)?;
+  }
+
+  Ok(data)
+}
+
+fn is_proof_type_ok(x: String) -> Result<(), String> {
+    if x == "pietrzak" || x == "wesolowski" {
+        Ok(())
+    } else {
+        Err("Invalid proof type".to_owned())
+    }
+}
+
+/// Subcommand reading the Json payload, with the options shared by all
+/// actions followed by its own `$arg`s
+macro_rules! payload_subcommand {
+    ($name:ident, $about:expr $(, $arg:tt)*) => {
+        clap_app!($name =>
+            (about: $about)
+            (@arg TYPE: -t --type +takes_value {is_proof_type_ok} "The type of proof to generate")
+            (@arg LENGTH: -l --length +takes_value {is_length_ok} "Length in bits of the discriminant (default: 2048, minimum: 1024)")
+            (@arg PRETTY: -p --pretty "Pretty-print the JSON output")
+            (@arg INPUT: -i --input +takes_value conflicts_with[DATA] "Read the Json data from a file, or from stdin if set to -")
+            (@arg DATA: required_unless[INPUT] "Json data")
+            $($arg)*
+        )
+    };
+}
+
+fn main() {
   let matches = clap_app!(vdf =>
     (version: crate_version!())
     (author: "Formula Zero")
     (about: "Encryption and decryption using VDF(Verifiable Delay Functions) & PoseidonCipher")
-    
-    (@arg TYPE: -t --type +takes_value {validate_proof_type} "The type of proof to generate")
-    (@arg LENGTH: -l --length +takes_value {is_u16_ok} "Length in bits of the discriminant (default: 2048)")
+    (@setting SubcommandRequiredElseHelp)
 
     (@arg VERBOSE: -v --verbose "Log verbosely to stderr.  This command does not currently log anything, so this option currently has no affect.")
-    
-    (@arg ACTION_TYPE: +required "encrypt / decrypt" )
-
-    (@arg DATA: +required "Json data" )
     // (@arg NUM_ITERATIONS: +required {is_u64_ok} "The number of iterations")
     // (@arg MESSAGE: +required "The message which can be raw tx or encoded tx")
     // (@arg NONCE: "The nonce for decryption")
-  ).get_matches();
+  )
+  .subcommand(payload_subcommand!(encrypt, "Encrypts originalText under the VDF output of x and t"))
+  .subcommand(payload_subcommand!(
+    decrypt,
+    "Decrypts cipherText with the VDF output of x and t",
+    (@arg MAX_BLOCKS: --("max-blocks") +takes_value {is_u64_ok} "Maximum number of cipher blocks accepted (default: 65536)")
+  ))
+  .subcommand(payload_subcommand!(verify, "Checks a VDF proof of x and t"))
+  .subcommand(clap_app!(estimate =>
+    (about: "Projects the time this machine needs to evaluate the VDF for t iterations")
+    (@arg LENGTH: -l --length +takes_value {is_length_ok} "Length in bits of the discriminant (default: 2048, minimum: 1024)")
+    (@arg PRETTY: -p --pretty "Pretty-print the JSON output")
+    (@arg NUM_ITERATIONS: +required {is_u64_ok} "The number of iterations")
+  ))
+  .get_matches();
+
+  let (action_type, matches) = match matches.subcommand() {
+    (action_type, Some(matches)) => (action_type, matches),
+    _ => unreachable!("a subcommand is required"),
+  };
+
+  if action_type == "estimate" {
+    let t = value_t!(matches, "NUM_ITERATIONS", u64).unwrap();
+    let length: u16 = matches.value_of("LENGTH").unwrap_or("2048").parse().unwrap();
+    let duration = estimate_eval_duration(length, t).expect("Length should have been validated earlier");
+
+    let output = EstimateOutput { t, length, seconds: duration.as_secs_f64() };
+    println!("{}", to_json(&output, matches.is_present("PRETTY")));
+    return;
+  }
 
   let data = r#"{"x": "aa1234", "t": 1000, "original_text": "hi_stompesi" }"#;
-  let data: &str = matches.value_of("DATA").unwrap();
+  let data = match matches.value_of("INPUT") {
+    Some(path) => read_input(path).unwrap_or_else(|e| {
+      eprintln!("Failed to read {}: {}", path, e);
+      std::process::exit(1);
+    }),
+    None => matches.value_of("DATA").unwrap().to_owned(),
+  };
 //   println!("data: {:?}", &data);
-  let data: Stompesi = serde_json::from_str(data).unwrap();;
+  let data: EncryptedInfo = serde_json::from_str(&data).unwrap_or_else(|e| {
+    eprintln!("Invalid Json data: {}", e);
+    std::process::exit(1);
+  });
 
   let is_pietrzak = matches.value_of("TYPE").map(|x| x == "pietrzak").unwrap_or(false);
-  let action_type: &str = matches.value_of("ACTION_TYPE").unwrap();
   let int_size_bits: u16 = matches.value_of("LENGTH").unwrap_or("2048").parse().unwrap();
+  let pretty = matches.is_present("PRETTY");
+  let max_blocks: usize = matches
+    .value_of("MAX_BLOCKS")
+    .map(|x| x.parse().unwrap())
+    .unwrap_or_else(PoseidonCipher::default_max_blocks);
 
   let vdf: Box<dyn VDF> = if is_pietrzak {
     Box::new(PietrzakVDFParams(int_size_bits).new()) as _
@@ -111,63 +319,250 @@ fn main() {
     Box::new(WesolowskiVDFParams(int_size_bits).new()) as _
   };
 
-  if action_type == "encrypt" {
-    let tx = data.original_text.as_bytes();
-    // println!("tx: {:?}", &data.original_text);
+  if action_type == "decrypt" {
+    if let Err(e) = PoseidonCipher::check_block_count(data.cipher_text.len(), max_blocks) {
+      eprintln!("{}", e);
+      std::process::exit(1);
+    }
+  }
+  let decoded = data.validate(action_type, &*vdf).unwrap_or_else(|e| {
+    eprintln!("{}", e);
+    std::process::exit(1);
+  });
 
-    let message_length = tx.len();
-    let bls_scalars = PoseidonCipher::convert_message_to_bls_scalar(&tx);
-    let message = PoseidonCipher::convert_bls_scalar_to_message(vec![bls_scalars[0].bls_scalar.clone()]);  
-    let messages = PoseidonCipher::generates_messages(bls_scalars);
-    let nonce = PoseidonCipher::gen_nonce();
+  match action_type {
+    "encrypt" => {
+      let tx = data.original_text.as_bytes();
+      // println!("tx: {:?}", &data.original_text);
 
-    let y = vdf.calculate_y(&hex::decode(&data.x).unwrap(), data.t).expect("Iterations should have been valiated earlier").try_into().expect("Slice with incorrect length");
-    let secret_key = PoseidonCipher::get_secret_key(y);
+      let message_length = tx.len();
+      let nonce = PoseidonCipher::gen_nonce();
 
-    let mut cipher_hexes = Vec::new();
+      let y = vdf.calculate_y(&decoded.x, data.t).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(1);
+      });
+      let secret_key = PoseidonCipher::get_secret_key_from_slice(&y);
 
-    for (_i, message) in messages.iter().enumerate() {
-      let cipher = PoseidonCipher::encrypt(&*message, &secret_key, &nonce);
-      let cipher_bytes = cipher.to_bytes();
-      cipher_hexes.push(hex::encode(cipher_bytes));
-    }
+      let cipher_hexes = PoseidonCipher::encrypt_message(tx, &secret_key, &nonce)
+        .iter()
+        .map(|cipher| hex::encode(cipher.to_bytes()))
+        .collect();
 
-    let mut result = Vec::new();
-    for (_i, cipher_hex) in cipher_hexes.iter().enumerate() {
-    //   println!("cipher_hex: {:?}", &cipher_hex);
-    //   println!("secret_key: {:?}", &secret_key);
-    //   println!("nonce: {:?}", &nonce);
-      let restored_cipher = PoseidonCipher::from_bytes(&hex::decode(cipher_hex).unwrap().try_into().unwrap()).unwrap();
+      let output = EncryptOutput {
+        version: SCHEMA_VERSION,
+        message_length,
+        nonce: hex::encode(nonce.to_bytes()),
+        x: &data.x,
+        t: data.t,
+        cipher_text: cipher_hexes,
+      };
+      println!("{}", to_json(&output, pretty));
+    }
+    "decrypt" => {
+      let nonce = PoseidonCipher::convert_nonce(&decoded.nonce).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(1);
+      });
+      let y = vdf.calculate_y(&decoded.x, data.t).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(1);
+      });
+      let secret_key = PoseidonCipher::get_secret_key_from_slice(&y);
 
-      let decrypt = restored_cipher.decrypt(&secret_key, &nonce);
-      result.extend_from_slice(&decrypt.unwrap()); 
+      let framed = PoseidonCipher::decrypt_blocks_with_max_blocks(&decoded.ciphers, &secret_key, &nonce, max_blocks)
+        .unwrap_or_else(|(index, _)| {
+          eprintln!("block {} failed to decrypt", index);
+          std::process::exit(1);
+        });
+      let message = PoseidonCipher::unframe_message(&framed).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(1);
+      });
+      let output = DecryptOutput {
+        version: SCHEMA_VERSION,
+        original_text: str::from_utf8(&message[..]).unwrap_or_else(|e| {
+          eprintln!("Decrypted message is not valid UTF-8: {}", e);
+          std::process::exit(1);
+        }),
+      };
+      println!("{}", to_json(&output, pretty));
     }
+    "verify" => {
+      let valid = vdf.verify(&decoded.x, data.t, &decoded.proof).is_ok();
+      println!("{}", to_json(&VerifyOutput { valid }, pretty));
+      if !valid {
+        std::process::exit(1);
+      }
+    }
+    _ => unreachable!("unknown subcommand {}", action_type),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
 
-    let mut message = PoseidonCipher::convert_bls_scalar_to_message(result);
-    message.resize(message_length, 0);
-    let result = str::from_utf8(&message[..]).unwrap();
-
-    println!("{{\"message_length\": {}, \"nonce\": {:?}, \"x\": {:?}, \"t\": {:?}, \"cipher_text\": {:?}}}", message_length, hex::encode(nonce.to_bytes()), data.x, data.t, cipher_hexes);
-  } else if action_type == "decrypt" {
-    let y = vdf.calculate_y(&hex::decode(data.x).unwrap(), data.t).expect("Iterations should have been valiated earlier").try_into().expect("Slice with incorrect length");
-    let secret_key = PoseidonCipher::get_secret_key(y);
-    let mut result = Vec::new();
-    
-    let nonce: [u8; 32] = hex::decode(data.nonce).unwrap().try_into().expect("Slice with incorrect length");
-    let nonce = PoseidonCipher::convert_nonce(&nonce);
-    for (_i, cipher_hex) in data.cipher_text.iter().enumerate() {
-    //   println!("cipher_hex: {:?}", &cipher_hex);
-    //   println!("secret_key: {:?}", &secret_key);
-    //   println!("nonce: {:?}", &nonce);
-      let restored_cipher = PoseidonCipher::from_bytes(&hex::decode(cipher_hex).unwrap().try_into().unwrap()).unwrap();
-
-      let decrypt = restored_cipher.decrypt(&secret_key, &nonce);
-      result.extend_from_slice(&decrypt.unwrap()); 
+  #[test]
+  fn legacy_and_camel_case_names_agree() {
+    let current: EncryptedInfo = serde_json::from_str(
+      r#"{"version": 1, "messageLength": 4, "nonce": "00", "originalText": "hi", "cipherText": ["ab"], "x": "aa", "t": 100}"#,
+    )
+    .unwrap();
+    let legacy: EncryptedInfo = serde_json::from_str(
+      r#"{"message_length": 4, "nonce": "00", "original_text": "hi", "cipher_text": ["ab"], "x": "aa", "t": 100}"#,
+    )
+    .unwrap();
+
+    assert_eq!(current, legacy);
+    assert_eq!(legacy.version, SCHEMA_VERSION);
+  }
+
+  #[test]
+  fn serializes_camel_case_names() {
+    let info: EncryptedInfo = serde_json::from_str(r#"{"x": "aa", "t": 100}"#).unwrap();
+    let value = serde_json::to_value(&info).unwrap();
+
+    for key in &["version", "messageLength", "nonce", "originalText", "cipherText", "proof", "x", "t"] {
+      assert!(value.get(key).is_some(), "missing {}", key);
     }
+  }
+
+  #[test]
+  fn pretty_and_compact_output_agree() {
+    let output = EncryptOutput {
+      version: SCHEMA_VERSION,
+      message_length: 2,
+      nonce: "00".to_owned(),
+      x: "aa",
+      t: 100,
+      cipher_text: vec!["ab".to_owned(), "cd".to_owned()],
+    };
+
+    let compact = to_json(&output, false);
+    let pretty = to_json(&output, true);
+
+    assert_eq!(serde_json::from_str::<Value>(&compact).unwrap(), serde_json::from_str::<Value>(&pretty).unwrap());
+    assert_eq!(compact, to_json(&output, false));
+    assert_eq!(
+      compact,
+      r#"{"version":1,"messageLength":2,"nonce":"00","x":"aa","t":100,"cipherText":["ab","cd"]}"#
+    );
+  }
+
+  #[test]
+  fn encrypt_output_round_trips_into_encrypted_info() {
+    let output = EncryptOutput {
+      version: SCHEMA_VERSION,
+      message_length: 11,
+      nonce: "0a0b".to_owned(),
+      x: "a\"quoted\\x",
+      t: 100,
+      cipher_text: vec!["ab".to_owned(), "cd".to_owned()],
+    };
+
+    let info: EncryptedInfo = serde_json::from_str(&to_json(&output, false)).unwrap();
+
+    assert_eq!(
+      info,
+      EncryptedInfo {
+        version: output.version,
+        message_length: output.message_length,
+        nonce: output.nonce,
+        original_text: String::new(),
+        cipher_text: output.cipher_text,
+        proof: String::new(),
+        x: output.x.to_owned(),
+        t: output.t,
+      }
+    );
+  }
+
+  #[test]
+  fn decrypt_output_is_escaped_json() {
+    let output = DecryptOutput { version: SCHEMA_VERSION, original_text: "say \"hi\"\\" };
+    let value: Value = serde_json::from_str(&to_json(&output, false)).unwrap();
+
+    assert_eq!(value["originalText"], "say \"hi\"\\");
+  }
+
+  fn vdf() -> Box<dyn VDF> {
+    Box::new(WesolowskiVDFParams(2048).new())
+  }
 
-    let mut message = PoseidonCipher::convert_bls_scalar_to_message(result);
-    message.resize(data.message_length, 0);
-    let result = str::from_utf8(&message[..]).unwrap();
-    println!("result: {:?}", &result);
+  fn info(json: &str) -> EncryptedInfo {
+    serde_json::from_str(json).unwrap()
   }
-}
\ No newline at end of file
+
+  #[test]
+  fn decrypt_requires_its_fields() {
+    let cipher = "00".repeat(PoseidonCipher::cipher_size_bytes());
+    let payload = |nonce: &str, cipher_text: &str| {
+      info(&format!(r#"{{"x": "aa", "t": 100, "nonce": "{}", "cipherText": [{}]}}"#, nonce, cipher_text))
+    };
+
+    let missing_nonce = info(&format!(r#"{{"x": "aa", "t": 100, "cipherText": ["{}"]}}"#, cipher));
+    assert_eq!(missing_nonce.validate("decrypt", &*vdf()).unwrap_err().to_string(), "decrypt requires field `nonce`");
+    assert_eq!(
+      payload("00", "").validate("decrypt", &*vdf()).unwrap_err().to_string(),
+      "decrypt requires field `cipherText`"
+    );
+    assert_eq!(
+      info(r#"{"x": "", "t": 100}"#).validate("encrypt", &*vdf()).unwrap_err(),
+      PayloadError::MissingField { action: "encrypt".to_owned(), field: "x" }
+    );
+
+    let decoded = payload("0a0b", &format!(r#""{}""#, cipher)).validate("decrypt", &*vdf()).unwrap();
+    assert_eq!(decoded.x, vec![0xaa]);
+    assert_eq!(decoded.nonce, vec![0x0a, 0x0b]);
+    assert_eq!(decoded.ciphers, vec![PoseidonCipher::default()]);
+  }
+
+  #[test]
+  fn hex_fields_must_decode() {
+    let odd = info(r#"{"x": "aa", "t": 100, "nonce": "abc", "cipherText": ["00"]}"#);
+    assert_eq!(odd.validate("decrypt", &*vdf()).unwrap_err().to_string(), "Invalid hex in nonce: Odd number of digits");
+
+    let odd_cipher = info(r#"{"x": "aa", "t": 100, "nonce": "00", "cipherText": ["0"]}"#);
+    assert_eq!(
+      odd_cipher.validate("decrypt", &*vdf()).unwrap_err().to_string(),
+      "Invalid hex in cipherText[0]: Odd number of digits"
+    );
+
+    let short_cipher = info(r#"{"x": "aa", "t": 100, "nonce": "00", "cipherText": ["00"]}"#);
+    assert_eq!(short_cipher.validate("decrypt", &*vdf()).unwrap_err(), PayloadError::InvalidCipher { index: 0 });
+
+    assert_eq!(
+      info(r#"{"x": "aa", "t": 100, "proof": "zz"}"#).validate("verify", &*vdf()).unwrap_err().to_string(),
+      "Invalid hex in proof: Invalid character 'z' at position 0"
+    );
+    // Fields the action does not use are not checked
+    assert!(info(r#"{"x": "aa", "t": 100, "nonce": "abc"}"#).validate("encrypt", &*vdf()).is_ok());
+  }
+
+  #[test]
+  fn t_must_be_a_valid_difficulty() {
+    let payload = |t: u64| info(&format!(r#"{{"x": "aa", "t": {}}}"#, t));
+
+    assert!(payload(100).validate("encrypt", &*vdf()).is_ok());
+    assert_eq!(
+      payload(0).validate("encrypt", &*vdf()).unwrap_err().to_string(),
+      "Invalid t: The number of iterations must be positive"
+    );
+    assert_eq!(
+      payload(1 << 53).validate("decrypt", &*vdf()).unwrap_err(),
+      PayloadError::InvalidField { field: "t", reason: "The number of iterations must be less than 2^53".to_owned() }
+    );
+  }
+
+  #[test]
+  fn newer_versions_are_rejected() {
+    let payload = |version: u32| info(&format!(r#"{{"version": {}, "x": "aa", "t": 100}}"#, version));
+
+    assert!(payload(SCHEMA_VERSION).validate("encrypt", &*vdf()).is_ok());
+    assert_eq!(
+      payload(SCHEMA_VERSION + 1).validate("encrypt", &*vdf()).unwrap_err().to_string(),
+      "Invalid version: 2 is newer than the supported version 1"
+    );
+  }
+}
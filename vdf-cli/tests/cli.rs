@@ -0,0 +1,179 @@
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
+use cipher::PoseidonCipher;
+use dusk_bytes::Serializable;
+use vdf::{VDFParams, WesolowskiVDFParams, VDF};
+
+fn run(args: &[&str]) -> Output {
+  Command::new(env!("CARGO_BIN_EXE_vdf-cli")).args(args).output().expect("failed to run vdf-cli")
+}
+
+fn run_with_stdin(args: &[&str], input: &str) -> Output {
+  let mut child = Command::new(env!("CARGO_BIN_EXE_vdf-cli"))
+    .args(args)
+    .stdin(Stdio::piped())
+    .stdout(Stdio::piped())
+    .stderr(Stdio::piped())
+    .spawn()
+    .expect("failed to run vdf-cli");
+  child.stdin.take().unwrap().write_all(input.as_bytes()).unwrap();
+  child.wait_with_output().unwrap()
+}
+
+fn vdf_cli(args: &[&str]) -> String {
+  let output = run(args);
+  assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+  String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn multi_block_round_trip() {
+  let message = "a message long enough to span several cipher blocks, ".repeat(4);
+  let request = serde_json::json!({"x": "aa", "t": 100, "originalText": message});
+
+  let encrypted: serde_json::Value = serde_json::from_str(&vdf_cli(&["encrypt", &request.to_string()])).unwrap();
+  assert!(encrypted["cipherText"].as_array().unwrap().len() > 1);
+
+  let decrypted: serde_json::Value = serde_json::from_str(&vdf_cli(&["decrypt", &encrypted.to_string()])).unwrap();
+  assert_eq!(decrypted["originalText"], message);
+}
+
+#[test]
+fn decrypt_script_fixture() {
+  // Regenerate with script/encrypt.sh whenever the ciphertext format changes
+  let fixture = concat!(env!("CARGO_MANIFEST_DIR"), "/../script/data/decryption_info.json");
+  let decrypted: serde_json::Value = serde_json::from_str(&vdf_cli(&["decrypt", "-i", fixture, "-t", "wesolowski"])).unwrap();
+  assert_eq!(decrypted["originalText"], "kasdlfjkasldjflaskdjflkasdf");
+}
+
+#[test]
+fn verify_proof() {
+  let proof = hex::encode(WesolowskiVDFParams(2048).new().solve(&[0xaa], 100).unwrap());
+  let request = |t: u64| serde_json::json!({"x": "aa", "t": t, "proof": proof}).to_string();
+
+  // Nothing but the result is written to stdout
+  assert_eq!(vdf_cli(&["verify", &request(100)]), "{\"valid\":true}\n");
+
+  let output = run(&["verify", &request(102)]);
+  assert!(!output.status.success());
+  assert_eq!(String::from_utf8(output.stdout).unwrap(), "{\"valid\":false}\n");
+
+  let output = run(&["verify", r#"{"x": "aa", "t": 100, "proof": "not hex"}"#]);
+  assert!(!output.status.success());
+  assert!(String::from_utf8(output.stderr).unwrap().contains("Invalid hex in proof"));
+}
+
+#[test]
+fn input_from_stdin_or_file() {
+  let request = serde_json::json!({"x": "aa", "t": 100, "originalText": "read from stdin"}).to_string();
+  let encrypted = vdf_cli(&["encrypt", &request]);
+  let inline = vdf_cli(&["decrypt", &encrypted]);
+
+  let piped = run_with_stdin(&["decrypt", "--input", "-"], &encrypted);
+  assert!(piped.status.success(), "{}", String::from_utf8_lossy(&piped.stderr));
+  assert_eq!(String::from_utf8(piped.stdout).unwrap(), inline);
+
+  let path = std::env::temp_dir().join(format!("vdf-cli-input-{}.json", std::process::id()));
+  std::fs::write(&path, &encrypted).unwrap();
+  let from_file = vdf_cli(&["decrypt", "-i", path.to_str().unwrap()]);
+  std::fs::remove_file(&path).unwrap();
+  assert_eq!(from_file, inline);
+
+  assert!(!run(&["decrypt", "--input", "-", &encrypted]).status.success());
+}
+
+#[test]
+fn length_flag() {
+  let request = serde_json::json!({"x": "aa", "t": 100, "originalText": "a 1024 bits discriminant"}).to_string();
+
+  let encrypted = vdf_cli(&["encrypt", "-l", "1024", &request]);
+  let decrypted: serde_json::Value = serde_json::from_str(&vdf_cli(&["decrypt", "-l", "1024", &encrypted])).unwrap();
+  assert_eq!(decrypted["originalText"], "a 1024 bits discriminant");
+
+  let output = run(&["encrypt", "-l", "512", &request]);
+  assert!(!output.status.success());
+  assert!(String::from_utf8(output.stderr).unwrap().contains("at least 1024 bits"));
+}
+
+#[test]
+fn subcommands() {
+  let output = run(&["encrpyt", r#"{"x": "aa", "t": 100}"#]);
+  assert!(!output.status.success());
+
+  let help = vdf_cli(&["--help"]);
+  for subcommand in &["encrypt", "decrypt", "verify", "estimate"] {
+    assert!(help.contains(subcommand), "missing {}", subcommand);
+  }
+  assert!(vdf_cli(&["decrypt", "--help"]).contains("--max-blocks"));
+  assert!(!vdf_cli(&["encrypt", "--help"]).contains("--max-blocks"));
+}
+
+#[test]
+fn estimate() {
+  let estimate = |t: &str| -> f64 {
+    let output: serde_json::Value = serde_json::from_str(&vdf_cli(&["estimate", "-l", "1024", t])).unwrap();
+    assert_eq!(output["length"], 1024);
+    output["seconds"].as_f64().unwrap()
+  };
+  assert!(estimate("100000") > 0.0);
+
+  assert!(!run(&["estimate", "-l", "512", "100000"]).status.success());
+  assert!(!run(&["estimate", "many"]).status.success());
+}
+
+#[test]
+fn invalid_payloads() {
+  let output = run(&["decrypt", r#"{"x": "aa", "t": 100, "cipherText": ["00"]}"#]);
+  assert!(!output.status.success());
+  assert!(String::from_utf8(output.stderr).unwrap().contains("decrypt requires field `nonce`"));
+
+  let output = run(&["decrypt", r#"{"x": "aa", "t": 100, "nonce": "abc", "cipherText": ["00"]}"#]);
+  assert!(!output.status.success());
+  assert!(String::from_utf8(output.stderr).unwrap().contains("Invalid hex in nonce"));
+
+  for t in &["0", "9007199254740992"] {
+    let output = run(&["encrypt", &format!(r#"{{"x": "aa", "t": {}, "originalText": "hi"}}"#, t)]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8(output.stderr).unwrap().contains("Invalid t"));
+  }
+
+  let output = run(&["encrypt", r#"{"version": 99, "x": "aa", "t": 100, "originalText": "hi"}"#]);
+  assert_eq!(output.status.code(), Some(1));
+  assert!(String::from_utf8(output.stderr).unwrap().contains("Invalid version"));
+
+  let output = run(&["encrypt", r#"{"t": 100}"#]);
+  assert!(!output.status.success());
+  assert!(String::from_utf8(output.stderr).unwrap().contains("Invalid Json data"));
+}
+
+#[test]
+fn decryption_failures() {
+  let message = "a message long enough to span several cipher blocks, ".repeat(4);
+  let request = serde_json::json!({"x": "aa", "t": 100, "originalText": message});
+  let encrypted: serde_json::Value = serde_json::from_str(&vdf_cli(&["encrypt", &request.to_string()])).unwrap();
+
+  let mut wrong_t = encrypted.clone();
+  wrong_t["t"] = 102.into();
+  let output = run(&["decrypt", &wrong_t.to_string()]);
+  assert_eq!(output.status.code(), Some(1));
+  assert!(String::from_utf8(output.stderr).unwrap().contains("block 0 failed to decrypt"));
+
+  let mut tampered = encrypted.clone();
+  tampered["cipherText"][1] = encrypted["cipherText"][0].clone();
+  let output = run(&["decrypt", &tampered.to_string()]);
+  assert_eq!(output.status.code(), Some(1));
+  assert!(String::from_utf8(output.stderr).unwrap().contains("block 1 failed to decrypt"));
+
+  let y = WesolowskiVDFParams(2048).new().calculate_y(&[0xaa], 100).unwrap();
+  let nonce = PoseidonCipher::gen_nonce();
+  let ciphers = PoseidonCipher::encrypt_message(&[0xff, 0xfe], &PoseidonCipher::get_secret_key_from_slice(&y), &nonce);
+  let not_utf8 = serde_json::json!({
+    "x": "aa",
+    "t": 100,
+    "nonce": hex::encode(nonce.to_bytes()),
+    "cipherText": ciphers.iter().map(|cipher| hex::encode(cipher.to_bytes())).collect::<Vec<_>>(),
+  });
+  let output = run(&["decrypt", &not_utf8.to_string()]);
+  assert_eq!(output.status.code(), Some(1));
+  assert!(String::from_utf8(output.stderr).unwrap().contains("not valid UTF-8"));
+}
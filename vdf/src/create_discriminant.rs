@@ -28,21 +28,19 @@
 
 include!(concat!(env!("OUT_DIR"), "/constants.rs"));
 
+use super::hash::{Sha256Hash, VDFHash};
 use classgroup::BigNumExt;
 use num_traits::Zero;
-use sha2::{digest::FixedOutput, Digest, Sha256};
-use std::u16;
+use alloc::vec::Vec;
+use core::u16;
 
-fn random_bytes_from_seed(seed: &[u8], byte_count: usize) -> Vec<u8> {
+fn random_bytes_from_seed<H: VDFHash>(seed: &[u8], byte_count: usize) -> Vec<u8> {
     assert!(byte_count <= 32 * ((1 << 16) - 1));
     let mut blob = Vec::with_capacity(byte_count);
     let mut extra: u16 = 0;
     while blob.len() < byte_count {
-        let mut hasher = Sha256::new();
-        hasher.input(seed);
         let extra_bits: [u8; 2] = [((extra & 0xFF00) >> 8) as _, (extra & 0xFF) as _];
-        hasher.input(&extra_bits);
-        blob.extend_from_slice(&hasher.fixed_result()[..]);
+        blob.extend_from_slice(&H::digest(&[seed, &extra_bits]));
         extra += 1;
     }
     blob.resize(byte_count, 0);
@@ -62,6 +60,12 @@ fn random_bytes_from_seed(seed: &[u8], byte_count: usize) -> Vec<u8> {
 /// This function is guaranteed not to panic for any inputs whatsoever, unless
 /// memory allocation fails and the allocator in use panics in that case.
 pub fn create_discriminant<T: BigNumExt>(seed: &[u8], length: u16) -> T {
+    create_discriminant_with_hash::<T, Sha256Hash>(seed, length)
+}
+
+/// Same as `create_discriminant`, but expands the seed with `H` instead of
+/// sha256.  Different hash functions give different discriminants.
+pub fn create_discriminant_with_hash<T: BigNumExt, H: VDFHash>(seed: &[u8], length: u16) -> T {
     let (mut n, residue) = {
         // The number of “extra” bits (that don’t evenly fit in a byte)
         let extra: u8 = (length as u8) & 7;
@@ -69,7 +73,7 @@ pub fn create_discriminant<T: BigNumExt>(seed: &[u8], length: u16) -> T {
         // The number of random bytes needed (the number of bytes that hold `length`
         // bits, plus 2).
         let random_bytes_len = ((usize::from(length) + 7) >> 3) + 2;
-        let random_bytes = random_bytes_from_seed(seed, random_bytes_len);
+        let random_bytes = random_bytes_from_seed::<H>(seed, random_bytes_len);
         let (n, last_2) = random_bytes.split_at(random_bytes_len - 2);
         let numerator = (usize::from(last_2[0]) << 8) + usize::from(last_2[1]);
 
@@ -124,7 +128,7 @@ pub fn create_discriminant<T: BigNumExt>(seed: &[u8], length: u16) -> T {
     }
 }
 
-#[cfg(test)]
+#[cfg(all(test, feature = "std"))]
 mod test {
     use super::*;
     use classgroup::{gmp_classgroup::GmpClassGroup, ClassGroup};
@@ -177,11 +181,11 @@ mod test {
     #[test]
     fn check_random_bytes() {
         assert_eq!(
-            &random_bytes_from_seed(b"\xaa", 7),
+            &random_bytes_from_seed::<Sha256Hash>(b"\xaa", 7),
             b"\x9f\x9d*\xe5\xe7<\xcb"
         );
         assert_eq!(
-            &random_bytes_from_seed(b"\xaa", 258)[..],
+            &random_bytes_from_seed::<Sha256Hash>(b"\xaa", 258)[..],
             &b"\x9f\x9d*\xe5\xe7<\xcbq\xa4q\x8e\
                    \xbc\xf0\xe3:\xa2\x98\xf8\xbd\xdc\xaa\xcbi\xcb\x10\xff\x0e\xafv\xdb\xec!\xc4K\
                    \xc6Jf\xf3\xa5\xda.7\xb7\xef\x87I\x85\xb8YX\xfc\xf2\x03\xa1\x8f4\xaf`\xab\xae]n\
@@ -0,0 +1,100 @@
+// Copyright 2018 Chia Network Inc and POA Networks Ltd.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//! Estimation of the time needed to evaluate a VDF.
+//!
+//! Evaluating the VDF for a difficulty `t` performs `t` sequential squarings
+//! in a class group, whichever proof is then generated.  Class groups have no
+//! trapdoor, so the prover and everyone else pay the same cost.  The estimate
+//! is obtained by timing a few squarings on this machine and extrapolating
+//! linearly; it does not include the time spent generating a proof.
+
+use super::{create_discriminant::create_discriminant, proof_of_time::check_int_size_bits, InvalidIterations};
+use classgroup::{gmp_classgroup::GmpClassGroup, ClassGroup};
+use std::time::{Duration, Instant};
+
+/// Number of squarings timed by `SquaringRate::measure`
+const SAMPLE_SQUARINGS: u64 = 1000;
+
+/// The time taken by one squaring with a discriminant of a given size.
+#[derive(Clone, Copy, Eq, PartialEq, Hash, Debug)]
+pub struct SquaringRate {
+    int_size_bits: u16,
+    per_squaring: Duration,
+}
+
+impl SquaringRate {
+    /// Times squarings with a discriminant of `int_size_bits` bits.
+    pub fn measure(int_size_bits: u16) -> Result<Self, InvalidIterations> {
+        check_int_size_bits(int_size_bits)?;
+        let discriminant = create_discriminant(b"\xaa", int_size_bits);
+        let mut x = GmpClassGroup::generator_for_discriminant(discriminant);
+
+        let start = Instant::now();
+        x.repeated_square(SAMPLE_SQUARINGS);
+        Ok(Self {
+            int_size_bits,
+            per_squaring: start.elapsed() / SAMPLE_SQUARINGS as u32,
+        })
+    }
+
+    /// The size of the discriminant, in bits.
+    pub fn int_size_bits(&self) -> u16 {
+        self.int_size_bits
+    }
+
+    /// The measured time of a single squaring.
+    pub fn per_squaring(&self) -> Duration {
+        self.per_squaring
+    }
+
+    /// The projected time of `difficulty` squarings.
+    pub fn estimate(&self, difficulty: u64) -> Duration {
+        self.per_squaring.mul_f64(difficulty as f64)
+    }
+}
+
+/// Projects the time needed to evaluate a VDF of difficulty `difficulty` with
+/// a discriminant of `int_size_bits` bits on this machine.
+pub fn estimate_eval_duration(int_size_bits: u16, difficulty: u64) -> Result<Duration, InvalidIterations> {
+    Ok(SquaringRate::measure(int_size_bits)?.estimate(difficulty))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn estimate_extrapolates_linearly() {
+        let rate = SquaringRate {
+            int_size_bits: 512,
+            per_squaring: Duration::from_micros(3),
+        };
+        assert_eq!(rate.int_size_bits(), 512);
+        assert_eq!(rate.per_squaring(), Duration::from_micros(3));
+
+        assert_eq!(rate.estimate(0), Duration::from_secs(0));
+        assert_eq!(rate.estimate(1), Duration::from_micros(3));
+        assert_eq!(rate.estimate(20_000), Duration::from_millis(60));
+        assert_eq!(rate.estimate(1 << 30), Duration::from_micros(3 << 30));
+        for &t in &[1, 1000, 20_000, 1 << 30] {
+            assert_eq!(rate.estimate(2 * t), rate.estimate(t) * 2);
+        }
+    }
+
+    #[test]
+    fn invalid_size() {
+        assert!(SquaringRate::measure(0).is_err());
+        assert!(estimate_eval_duration(0, 100).is_err());
+    }
+}
@@ -0,0 +1,207 @@
+// Copyright 2018 Chia Network Inc and POA Networks Ltd.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//! C interface to the Wesolowski VDF, available with the `ffi` feature.
+//!
+//! Every function returns `VDF_OK` on success and one of the negative
+//! `VDF_ERR_*` codes otherwise.  Buffers written to a `VdfBuffer` are owned by
+//! the caller, who must release each of them exactly once with `vdf_free`.
+//!
+//! A C header can be generated with [cbindgen] from the `vdf` directory:
+//!
+//! ```sh
+//! $ cbindgen --config cbindgen.toml --output vdf.h
+//! ```
+//!
+//! and a shared library built with
+//! `cargo rustc --release --features ffi --crate-type cdylib`.
+//!
+//! [cbindgen]: https://github.com/eqrion/cbindgen
+
+use super::{VDFParams, WesolowskiProof, WesolowskiVDFParams, VDF};
+use std::{panic, ptr, slice};
+
+/// The call succeeded
+pub const VDF_OK: i32 = 0;
+/// A required pointer was null
+pub const VDF_ERR_NULL_POINTER: i32 = -1;
+/// The difficulty or the discriminant size is not supported
+pub const VDF_ERR_INVALID_DIFFICULTY: i32 = -2;
+/// The proof does not verify
+pub const VDF_ERR_INVALID_PROOF: i32 = -3;
+/// The computation panicked
+pub const VDF_ERR_PANIC: i32 = -4;
+
+/// A byte buffer allocated by this library
+#[repr(C)]
+pub struct VdfBuffer {
+    pub ptr: *mut u8,
+    pub len: usize,
+}
+
+impl VdfBuffer {
+    fn from_vec(v: Vec<u8>) -> Self {
+        let len = v.len();
+        let ptr = Box::into_raw(v.into_boxed_slice()) as *mut u8;
+        VdfBuffer { ptr, len }
+    }
+}
+
+unsafe fn input<'a>(ptr: *const u8, len: usize) -> Option<&'a [u8]> {
+    if len == 0 {
+        Some(&[])
+    } else if ptr.is_null() {
+        None
+    } else {
+        Some(slice::from_raw_parts(ptr, len))
+    }
+}
+
+/// Evaluates the VDF on the challenge `x` for `t` iterations, with a
+/// discriminant of `int_size_bits` bits, and writes the output `y` and the
+/// proof `pi` to `out_y` and `out_pi`.
+///
+/// # Safety
+///
+/// `x_ptr` must point to `x_len` readable bytes (it may be null if `x_len` is
+/// zero), and `out_y` and `out_pi` must point to writable `VdfBuffer`s.
+#[no_mangle]
+pub unsafe extern "C" fn vdf_eval(
+    x_ptr: *const u8,
+    x_len: usize,
+    t: u64,
+    int_size_bits: u16,
+    out_y: *mut VdfBuffer,
+    out_pi: *mut VdfBuffer,
+) -> i32 {
+    let x = match input(x_ptr, x_len) {
+        Some(x) if !out_y.is_null() && !out_pi.is_null() => x,
+        _ => return VDF_ERR_NULL_POINTER,
+    };
+    match panic::catch_unwind(|| WesolowskiVDFParams(int_size_bits).new().solve(x, t)) {
+        Ok(Ok(blob)) => {
+            let proof = WesolowskiProof::from_bytes(&blob, int_size_bits)
+                .expect(super::INCORRECT_BUFFER_SIZE);
+            ptr::write(out_y, VdfBuffer::from_vec(proof.y));
+            ptr::write(out_pi, VdfBuffer::from_vec(proof.proof));
+            VDF_OK
+        }
+        Ok(Err(_)) => VDF_ERR_INVALID_DIFFICULTY,
+        Err(_) => VDF_ERR_PANIC,
+    }
+}
+
+/// Verifies that `y` and `pi` are the output and proof of `vdf_eval` for the
+/// same `x`, `t` and `int_size_bits`.
+///
+/// # Safety
+///
+/// Each pointer must point to the given number of readable bytes (it may be
+/// null if that number is zero).
+#[no_mangle]
+pub unsafe extern "C" fn vdf_verify(
+    x_ptr: *const u8,
+    x_len: usize,
+    t: u64,
+    int_size_bits: u16,
+    y_ptr: *const u8,
+    y_len: usize,
+    pi_ptr: *const u8,
+    pi_len: usize,
+) -> i32 {
+    let (x, y, pi) = match (input(x_ptr, x_len), input(y_ptr, y_len), input(pi_ptr, pi_len)) {
+        (Some(x), Some(y), Some(pi)) => (x, y, pi),
+        _ => return VDF_ERR_NULL_POINTER,
+    };
+    let proof = WesolowskiProof {
+        int_size_bits,
+        y: y.to_vec(),
+        proof: pi.to_vec(),
+    };
+    match panic::catch_unwind(|| {
+        WesolowskiVDFParams(int_size_bits)
+            .new()
+            .verify_proof(x, t, &proof)
+    }) {
+        Ok(Ok(())) => VDF_OK,
+        Ok(Err(_)) => VDF_ERR_INVALID_PROOF,
+        Err(_) => VDF_ERR_PANIC,
+    }
+}
+
+/// Releases a buffer written by `vdf_eval`.
+///
+/// # Safety
+///
+/// `buffer` must have been written by this library and not freed before.
+#[no_mangle]
+pub unsafe extern "C" fn vdf_free(buffer: VdfBuffer) {
+    if !buffer.ptr.is_null() {
+        drop(Box::from_raw(ptr::slice_from_raw_parts_mut(buffer.ptr, buffer.len)));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::mem::MaybeUninit;
+
+    #[test]
+    fn round_trip() {
+        let x = b"\xaa";
+        let mut y = MaybeUninit::<VdfBuffer>::uninit();
+        let mut pi = MaybeUninit::<VdfBuffer>::uninit();
+        unsafe {
+            let status = vdf_eval(x.as_ptr(), x.len(), 100, 512, y.as_mut_ptr(), pi.as_mut_ptr());
+            assert_eq!(status, VDF_OK);
+            let (y, pi) = (y.assume_init(), pi.assume_init());
+            assert_eq!(y.len, WesolowskiProof::element_len(512));
+
+            let verify = |t| vdf_verify(x.as_ptr(), x.len(), t, 512, y.ptr, y.len, pi.ptr, pi.len);
+            assert_eq!(verify(100), VDF_OK);
+            assert_eq!(verify(102), VDF_ERR_INVALID_PROOF);
+            assert_eq!(
+                vdf_verify(x.as_ptr(), x.len(), 100, 512, y.ptr, y.len - 1, pi.ptr, pi.len),
+                VDF_ERR_INVALID_PROOF
+            );
+
+            vdf_free(y);
+            vdf_free(pi);
+        }
+    }
+
+    #[test]
+    fn invalid_arguments() {
+        let x = b"\xaa";
+        let mut y = MaybeUninit::<VdfBuffer>::uninit();
+        let mut pi = MaybeUninit::<VdfBuffer>::uninit();
+        unsafe {
+            assert_eq!(
+                vdf_eval(ptr::null(), 1, 100, 512, y.as_mut_ptr(), pi.as_mut_ptr()),
+                VDF_ERR_NULL_POINTER
+            );
+            assert_eq!(
+                vdf_eval(x.as_ptr(), x.len(), 100, 512, ptr::null_mut(), pi.as_mut_ptr()),
+                VDF_ERR_NULL_POINTER
+            );
+            assert_eq!(
+                vdf_eval(x.as_ptr(), x.len(), 0, 512, y.as_mut_ptr(), pi.as_mut_ptr()),
+                VDF_ERR_INVALID_DIFFICULTY
+            );
+            assert_eq!(
+                vdf_verify(x.as_ptr(), x.len(), 100, 512, ptr::null(), 4, ptr::null(), 0),
+                VDF_ERR_NULL_POINTER
+            );
+        }
+    }
+}
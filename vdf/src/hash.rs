@@ -0,0 +1,59 @@
+// Copyright 2018 Chia Network Inc and POA Networks Ltd.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//! Hash functions used to derive discriminants and primes.
+//!
+//! The hash function determines the discriminant created from a challenge and
+//! the prime used in a Wesolowski proof.  Changing it therefore changes every
+//! proof: the prover and the verifier must use the same one.
+
+use core::fmt::Debug;
+use sha2::{digest::FixedOutput, Digest, Sha256};
+
+/// A hash function with a 256-bit output.
+pub trait VDFHash: Clone + Debug + Send + Sync {
+    /// Hashes the concatenation of `parts`.
+    fn digest(parts: &[&[u8]]) -> [u8; 32];
+}
+
+/// SHA-256, the hash function used by default.
+#[derive(Clone, Copy, Eq, PartialEq, PartialOrd, Ord, Hash, Debug, Default)]
+pub struct Sha256Hash;
+
+impl VDFHash for Sha256Hash {
+    fn digest(parts: &[&[u8]]) -> [u8; 32] {
+        let mut hasher = Sha256::new();
+        for part in parts {
+            hasher.input(part);
+        }
+        let mut res = [0; 32];
+        res.copy_from_slice(&hasher.fixed_result()[..]);
+        res
+    }
+}
+
+/// BLAKE3, available with the `blake3` feature.
+#[cfg(feature = "blake3")]
+#[derive(Clone, Copy, Eq, PartialEq, PartialOrd, Ord, Hash, Debug, Default)]
+pub struct Blake3Hash;
+
+#[cfg(feature = "blake3")]
+impl VDFHash for Blake3Hash {
+    fn digest(parts: &[&[u8]]) -> [u8; 32] {
+        let mut hasher = blake3::Hasher::new();
+        for part in parts {
+            hasher.update(part);
+        }
+        *hasher.finalize().as_bytes()
+    }
+}
@@ -12,6 +12,7 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 #![deny(warnings)]
+#![cfg_attr(not(feature = "std"), no_std)]
 //! # Rust implementations of class groups and verifyable delay functions
 //!
 //! This repo includes three crates
@@ -45,6 +46,7 @@
 //!
 //! ```rust
 //! extern crate vdf;
+//! # #[cfg(feature = "std")]
 //! use vdf::{InvalidProof, PietrzakVDFParams, VDFParams, WesolowskiVDFParams, VDF};
 //! const CORRECT_SOLUTION: &[u8] =
 //!     b"\x00\x52\x71\xe8\xf9\xab\x2e\xb8\xa2\x90\x6e\x85\x1d\xfc\xb5\x54\x2e\x41\x73\xf0\x16\
@@ -61,6 +63,9 @@
 //!     \xdc\x42\x16\xbc\x16\xef\x7d\xac\x97\x0b\x0e\xe4\x6d\x69\x41\x6f\x9a\x9a\xce\xe6\x51\
 //!     \xd1\x58\xac\x64\x91\x5b";
 //!
+//! # #[cfg(not(feature = "std"))]
+//! # fn main() {}
+//! # #[cfg(feature = "std")]
 //! fn main() {
 //!     let pietrzak_vdf = PietrzakVDFParams(2048).new();
 //!     assert_eq!(
@@ -71,19 +76,39 @@
 //! }
 //! ```
 //!
+//! ### Without `std`
+//!
+//! The GMP-backed VDFs and the prover need the default `std` feature.  With
+//! `default-features = false` the crate is `#![no_std]` (it still needs
+//! `alloc`) and provides `WesolowskiProof::verify` for any `ClassGroup`
+//! implementation.
+//!
 //! ### To run the benchmarks
 //!
 //! Benchmarks are provided for the classgroup operations.  Run `cargo bench`
 //! to run them.  Additional benchmarks are under development.
-use classgroup;
+extern crate alloc;
 
 mod create_discriminant;
-use std::fmt::Debug;
+use alloc::{string::String, vec::Vec};
+use core::fmt::Debug;
 
+#[cfg(feature = "blake3")]
+pub use self::hash::Blake3Hash;
+pub use self::{
+    create_discriminant::{create_discriminant, create_discriminant_with_hash},
+    hash::{Sha256Hash, VDFHash},
+    proof_of_time::Checkpoint,
+    proof_wesolowski::{fiat_shamir_challenge, hash_prime, WesolowskiProof},
+};
+#[cfg(feature = "std")]
 pub use self::{
-    create_discriminant::create_discriminant,
+    estimate::{estimate_eval_duration, SquaringRate},
     proof_pietrzak::{PietrzakVDF, PietrzakVDFParams},
-    proof_wesolowski::{WesolowskiVDF, WesolowskiVDFParams},
+    proof_wesolowski::{
+        approximate_parameters, approximate_parameters_with_memory, WesolowskiVDF,
+        WesolowskiVDFParams, DEFAULT_MEMORY_BYTES,
+    },
 };
 
 /// Message used to report an internal miscalculation of serialization buffer
@@ -91,7 +116,13 @@ pub use self::{
 const INCORRECT_BUFFER_SIZE: &str =
     "internal error: incorrect buffer size calculation (this is a bug)";
 
+#[cfg(feature = "std")]
+mod estimate;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+mod hash;
 mod proof_of_time;
+#[cfg(feature = "std")]
 mod proof_pietrzak;
 mod proof_wesolowski;
 
@@ -109,6 +140,12 @@ pub struct InvalidProof;
 #[derive(Clone, Eq, PartialEq, PartialOrd, Ord, Hash, Debug)]
 pub struct InvalidIterations(String);
 
+impl core::fmt::Display for InvalidIterations {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
 /// The type of VDF parameters.
 ///
 /// Parameters represent public information that can be shared by all users
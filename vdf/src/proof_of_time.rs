@@ -11,9 +11,81 @@
 // WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
 // See the License for the specific language governing permissions and
 // limitations under the License.
-use super::classgroup::ClassGroup;
-use std::{collections::HashMap, usize};
+#[cfg(feature = "std")]
+use classgroup::ClassGroup;
+#[cfg(feature = "std")]
+use alloc::{collections::BTreeMap, vec};
+use super::InvalidIterations;
+use alloc::{format, vec::Vec};
+use core::convert::TryInto;
+#[cfg(feature = "std")]
+use core::usize;
 
+/// Checks that a discriminant of `int_size_bits` bits can be created.
+pub fn check_int_size_bits(int_size_bits: u16) -> Result<(), InvalidIterations> {
+    if int_size_bits < MIN_INT_SIZE_BITS {
+        Err(InvalidIterations(format!(
+            "The discriminant size must be at least {} bits",
+            MIN_INT_SIZE_BITS
+        )))
+    } else {
+        Ok(())
+    }
+}
+
+/// The smallest supported discriminant size.
+///
+/// `create_discriminant` moves its seed to a fixed residue modulo
+/// `M = 8 * 3 * 5 * 7 * 11 * 13 = 120120` and then searches upwards in steps of
+/// `M`.  Unless `2^(int_size_bits - 1)` exceeds `M`, the discriminant can end
+/// up far larger than `int_size_bits`, and its forms no longer fit the
+/// `(int_size_bits + 16) >> 4` bytes that `serialize` reserves per coefficient.
+pub const MIN_INT_SIZE_BITS: u16 = 18;
+
+/// An intermediate element of a squaring chain, together with the number of
+/// squarings that have already been applied to the initial element.
+///
+/// Checkpoints let a long computation be persisted and resumed later.
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+pub struct Checkpoint {
+    pub iterations: u64,
+    pub element: Vec<u8>,
+}
+
+impl Checkpoint {
+    /// Serializes the checkpoint as the big-endian iteration count followed by
+    /// the serialized group element.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut v = Vec::with_capacity(8 + self.element.len());
+        v.extend_from_slice(&self.iterations.to_be_bytes());
+        v.extend_from_slice(&self.element);
+        v
+    }
+
+    /// Parses a checkpoint produced by `Checkpoint::to_bytes`.  Returns `None`
+    /// if the input is too short to hold the iteration count.
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() < 8 {
+            return None;
+        }
+        let (iterations, element) = bytes.split_at(8);
+        Some(Self {
+            iterations: u64::from_be_bytes(iterations.try_into().ok()?),
+            element: element.to_vec(),
+        })
+    }
+}
+
+#[cfg(feature = "std")]
+pub fn serialize_element<V: ClassGroup>(x: &V, int_size_bits: usize) -> Vec<u8> {
+    let element_length = 2 * ((int_size_bits + 16) >> 4);
+    let mut v = vec![0; element_length];
+    x.serialize(&mut v[..])
+        .expect(super::INCORRECT_BUFFER_SIZE);
+    v
+}
+
+#[cfg(feature = "std")]
 pub fn serialize<V: ClassGroup>(proof: &[V], y: &V, int_size_bits: usize) -> Vec<u8> {
     let proof_len = proof.len();
     let element_length = 2 * ((int_size_bits + 16) >> 4);
@@ -29,6 +101,7 @@ pub fn serialize<V: ClassGroup>(proof: &[V], y: &V, int_size_bits: usize) -> Vec
     }
     v
 }
+#[cfg(feature = "std")]
 pub fn deserialize_proof<T>(
     proof_blob: &[u8],
     discriminant: &T::BigNum,
@@ -36,8 +109,8 @@ pub fn deserialize_proof<T>(
 ) -> Result<Vec<T>, ()>
 where
     T: ClassGroup,
-    for<'a, 'b> &'a T: std::ops::Mul<&'b T, Output = T>,
-    for<'a, 'b> &'a T::BigNum: std::ops::Mul<&'b T::BigNum, Output = T::BigNum>,
+    for<'a, 'b> &'a T: core::ops::Mul<&'b T, Output = T>,
+    for<'a, 'b> &'a T::BigNum: core::ops::Mul<&'b T::BigNum, Output = T::BigNum>,
 {
     let length = T::size_in_bits(discriminant);
     if length > usize::MAX - 16 {
@@ -68,14 +141,15 @@ where
     Ok(v)
 }
 
-pub fn iterate_squarings<V, U>(mut x: V, powers_to_calculate: U) -> HashMap<u64, V>
+#[cfg(feature = "std")]
+pub fn iterate_squarings<V, U>(mut x: V, powers_to_calculate: U) -> BTreeMap<u64, V>
 where
     V: ClassGroup,
-    for<'a, 'b> &'a V: std::ops::Mul<&'b V, Output = V>,
-    for<'a, 'b> &'a V::BigNum: std::ops::Mul<&'b V::BigNum, Output = V::BigNum>,
+    for<'a, 'b> &'a V: core::ops::Mul<&'b V, Output = V>,
+    for<'a, 'b> &'a V::BigNum: core::ops::Mul<&'b V::BigNum, Output = V::BigNum>,
     U: Iterator<Item = u64>,
 {
-    let mut powers_calculated = HashMap::new();
+    let mut powers_calculated = BTreeMap::new();
     let mut powers_to_calculate: Vec<u64> = powers_to_calculate.collect();
     
     // 정렬 함수
@@ -11,7 +11,7 @@
 // WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
 // See the License for the specific language governing permissions and
 // limitations under the License.
-use super::proof_of_time::{deserialize_proof, iterate_squarings, serialize};
+use super::proof_of_time::{check_int_size_bits, deserialize_proof, iterate_squarings, serialize};
 use classgroup::{gmp_classgroup::GmpClassGroup, BigNumExt, ClassGroup};
 use num_traits::{One, Zero};
 use std::{fmt, num::ParseIntError, ops::Index, str::FromStr, u64, usize};
@@ -407,6 +407,7 @@ impl super::VDF for PietrzakVDF {
             .map(drop)
     }
     fn solve(&self, challenge: &[u8], difficulty: u64) -> Result<Vec<u8>, Bad> {
+        check_int_size_bits(self.int_size_bits)?;
         Ok(create_proof_of_time_pietrzak::<GmpClassGroup>(
             challenge,
             Iterations::new(difficulty).map_err(|x| Bad(format!("{}", x)))?,
@@ -415,6 +416,7 @@ impl super::VDF for PietrzakVDF {
     }
 
     fn calculate_y(&self, challenge: &[u8], difficulty: u64) -> Result<Vec<u8>, Bad> {
+        check_int_size_bits(self.int_size_bits)?;
         Ok(create_proof_of_time_pietrzak::<GmpClassGroup>(
             challenge,
             Iterations::new(difficulty).map_err(|x| Bad(format!("{}", x)))?,
@@ -428,6 +430,7 @@ impl super::VDF for PietrzakVDF {
         difficulty: u64,
         alleged_solution: &[u8],
     ) -> Result<(), super::InvalidProof> {
+        check_int_size_bits(self.int_size_bits).map_err(|_| super::InvalidProof)?;
         check_proof_of_time_pietrzak::<GmpClassGroup>(
             challenge,
             alleged_solution,
@@ -471,4 +474,47 @@ mod test {
         q = (1..2).step_by(2).collect();
         assert_eq!(q[..], [1]);
     }
+    #[test]
+    fn valid_proof() {
+        use crate::{VDFParams, VDF};
+        let vdf = PietrzakVDFParams(512).new();
+        let proof = vdf.solve(b"\xaa", 534).unwrap();
+
+        assert!(vdf.verify(b"\xaa", 534, &proof).is_ok());
+    }
+
+    #[test]
+    fn tampered_midpoint() {
+        use crate::{VDFParams, VDF};
+        let vdf = PietrzakVDFParams(512).new();
+        let mut proof = vdf.solve(b"\xaa", 534).unwrap();
+        let element_len = 2 * ((512 + 16) >> 4);
+        assert!(proof.len() >= 3 * element_len);
+
+        // Swap the first two midpoints, which keeps every element well formed
+        let (first, second) = proof[element_len..].split_at_mut(element_len);
+        first.swap_with_slice(&mut second[..element_len]);
+        assert!(vdf.verify(b"\xaa", 534, &proof).is_err());
+    }
+
+    #[test]
+    fn wrong_difficulty() {
+        use crate::{VDFParams, VDF};
+        let vdf = PietrzakVDFParams(512).new();
+        let proof = vdf.solve(b"\xaa", 534).unwrap();
+
+        assert!(vdf.verify(b"\xaa", 536, &proof).is_err());
+        assert!(vdf.verify(b"\xaa", 532, &proof).is_err());
+        assert!(vdf.check_difficulty(535).is_err());
+    }
+
+    #[test]
+    fn zero_int_size_bits() {
+        use crate::{VDFParams, VDF};
+        let vdf = PietrzakVDFParams(0).new();
+
+        assert!(vdf.solve(b"\xaa", 534).is_err());
+        assert!(vdf.calculate_y(b"\xaa", 534).is_err());
+        assert!(vdf.verify(b"\xaa", 534, &[0; 4]).is_err());
+    }
 }
@@ -12,58 +12,166 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use super::proof_of_time::{iterate_squarings, serialize};
-use classgroup::{gmp_classgroup::GmpClassGroup, BigNum, BigNumExt, ClassGroup};
-use sha2::{digest::FixedOutput, Digest, Sha256};
-use std::{cmp::Eq, collections::HashMap, hash::Hash, mem, u64, usize};
+#[cfg(feature = "std")]
+use super::hash::Sha256Hash;
+use super::hash::VDFHash;
+use super::proof_of_time::check_int_size_bits;
+#[cfg(feature = "std")]
+use super::proof_of_time::{iterate_squarings, serialize, serialize_element, Checkpoint};
+use alloc::{vec, vec::Vec};
+#[cfg(feature = "std")]
+use classgroup::gmp_classgroup::GmpClassGroup;
+use classgroup::{BigNum, BigNumExt, ClassGroup};
+#[cfg(feature = "std")]
+use core::marker::PhantomData;
+use core::{u64, usize};
+#[cfg(all(feature = "std", feature = "rayon"))]
+use rayon::prelude::*;
+#[cfg(feature = "std")]
+use std::{cmp::Eq, collections::HashMap, hash::Hash, mem};
+
+/// A Wesolowski proof of a class-group VDF: the serialized output `y` of the
+/// squaring chain and the serialized proof element, both for a discriminant
+/// of `int_size_bits` bits.
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct WesolowskiProof {
+    pub int_size_bits: u16,
+    pub y: Vec<u8>,
+    pub proof: Vec<u8>,
+}
+
+impl WesolowskiProof {
+    /// Length of a serialized group element for `int_size_bits`.
+    pub fn element_len(int_size_bits: u16) -> usize {
+        2 * ((usize::from(int_size_bits) + 16) >> 4)
+    }
+
+    /// Serializes the proof as `y` followed by the proof element, the format
+    /// returned by `VDF::solve`.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut v = Vec::with_capacity(self.y.len() + self.proof.len());
+        v.extend_from_slice(&self.y);
+        v.extend_from_slice(&self.proof);
+        v
+    }
+
+    /// Parses a proof blob returned by `VDF::solve`.  Fails if the blob is not
+    /// exactly two group elements for `int_size_bits`.
+    pub fn from_bytes(proof_blob: &[u8], int_size_bits: u16) -> Result<Self, super::InvalidProof> {
+        let element_len = Self::element_len(int_size_bits);
+        if proof_blob.len() != 2 * element_len {
+            return Err(super::InvalidProof);
+        }
+        let (y, proof) = proof_blob.split_at(element_len);
+        Ok(Self {
+            int_size_bits,
+            y: y.to_vec(),
+            proof: proof.to_vec(),
+        })
+    }
 
+    /// Verifies the proof for `challenge` and `difficulty` in the class group
+    /// `V`, with the hash function `H`.  This is the only part of the crate
+    /// that is available without the `std` feature.
+    ///
+    /// ```
+    /// use classgroup::gmp_classgroup::GmpClassGroup;
+    /// use vdf::{Sha256Hash, WesolowskiProof};
+    ///
+    /// // `WesolowskiVDFParams(128).new().solve(b"\xaa", 1000)`
+    /// let blob = hex::decode("0036640fa7700e9739fff7ac8c61094d46e90032f2e3a35a212a24ffd44fb38040ddf2b1").unwrap();
+    /// let proof = WesolowskiProof::from_bytes(&blob, 128).unwrap();
+    /// assert!(proof.verify::<GmpClassGroup, Sha256Hash>(b"\xaa", 1000).is_ok());
+    /// assert!(proof.verify::<GmpClassGroup, Sha256Hash>(b"\xaa", 1001).is_err());
+    /// ```
+    pub fn verify<V: ClassGroup, H: VDFHash>(
+        &self,
+        challenge: &[u8],
+        difficulty: u64,
+    ) -> Result<(), super::InvalidProof>
+    where
+        V::BigNum: BigNumExt,
+    {
+        check_wesolowski_proof::<V::BigNum, V, H>(challenge, self, difficulty)
+            .map_err(|()| super::InvalidProof)
+    }
+
+    fn has_valid_lengths(&self) -> bool {
+        let element_len = Self::element_len(self.int_size_bits);
+        self.y.len() == element_len && self.proof.len() == element_len
+    }
+}
+
+#[cfg(feature = "std")]
 #[derive(Debug, Clone)]
-pub struct WesolowskiVDF {
+pub struct WesolowskiVDF<H: VDFHash = Sha256Hash> {
     int_size_bits: u16,
+    memory_bytes: u64,
+    hash: PhantomData<H>,
 }
+#[cfg(feature = "std")]
 use super::InvalidIterations as Bad;
 
+#[cfg(feature = "std")]
 #[derive(Clone, Copy, Eq, PartialEq, PartialOrd, Ord, Hash, Debug)]
 pub struct WesolowskiVDFParams(pub u16);
 
+#[cfg(feature = "std")]
 impl super::VDFParams for WesolowskiVDFParams {
     type VDF = WesolowskiVDF;
     fn new(self) -> Self::VDF {
+        self.new_with_memory(DEFAULT_MEMORY_BYTES)
+    }
+}
+
+#[cfg(feature = "std")]
+impl WesolowskiVDFParams {
+    /// Creates a VDF that budgets about `memory_bytes` of precomputed powers
+    /// when proving, instead of the default 10 MB.  The budget only trades
+    /// memory for proving time; proofs are the same for any budget.
+    pub fn new_with_memory(self, memory_bytes: u64) -> WesolowskiVDF {
         WesolowskiVDF {
             int_size_bits: self.0,
+            memory_bytes,
+            hash: PhantomData,
         }
     }
 }
 
-impl super::VDF for WesolowskiVDF {
-    fn check_difficulty(&self, _difficulty: u64) -> Result<(), Bad> {
-        Ok(())
-    }
-
-    fn solve(&self, challenge: &[u8], difficulty: u64) -> Result<Vec<u8>, Bad> {
-        if difficulty > usize::MAX as u64 {
+#[cfg(feature = "std")]
+impl<H: VDFHash> super::VDF for WesolowskiVDF<H> {
+    fn check_difficulty(&self, difficulty: u64) -> Result<(), Bad> {
+        if difficulty == 0 {
+            Err(Bad("The number of iterations must be positive".to_owned()))
+        } else if difficulty > usize::MAX as u64 {
             Err(Bad("Cannot have more that usize::MAX iterations".to_owned()))
+        } else if difficulty >= 1 << 53 {
+            // The segment parameters are computed with `f64`s
+            Err(Bad("The number of iterations must be less than 2^53".to_owned()))
         } else {
-            Ok(create_proof_of_time_wesolowski::<
-                <GmpClassGroup as ClassGroup>::BigNum,
-                GmpClassGroup,
-            >(
-                challenge, difficulty as usize, self.int_size_bits
-            ))
+            Ok(())
         }
     }
 
+    fn solve(&self, challenge: &[u8], difficulty: u64) -> Result<Vec<u8>, Bad> {
+        self.check_difficulty(difficulty)?;
+        create_proof_of_time_wesolowski::<<GmpClassGroup as ClassGroup>::BigNum, GmpClassGroup, H>(
+            challenge,
+            difficulty as usize,
+            self.int_size_bits,
+            self.memory_bytes,
+        )
+    }
+
     fn calculate_y(&self, challenge: &[u8], difficulty: u64) -> Result<Vec<u8>, Bad> {
-        if difficulty > usize::MAX as u64 {
-            Err(Bad("Cannot have more that usize::MAX iterations".to_owned()))
-        } else {
-            Ok(generate_y::<
-                <GmpClassGroup as ClassGroup>::BigNum,
-                GmpClassGroup,
-            >(
-                challenge, difficulty as usize, self.int_size_bits
-            ))
-        }
+        self.check_difficulty(difficulty)?;
+        generate_y::<<GmpClassGroup as ClassGroup>::BigNum, GmpClassGroup, H>(
+            challenge,
+            difficulty as usize,
+            self.int_size_bits,
+            self.memory_bytes,
+        )
     }
 
     fn verify(
@@ -72,21 +180,121 @@ impl super::VDF for WesolowskiVDF {
         difficulty: u64,
         alleged_solution: &[u8],
     ) -> Result<(), super::InvalidProof> {
-        check_proof_of_time_wesolowski::<<GmpClassGroup as ClassGroup>::BigNum, GmpClassGroup>(
-            challenge,
-            alleged_solution,
-            difficulty,
-            self.int_size_bits,
-        )
-        .map_err(|()| super::InvalidProof)
+        let proof = WesolowskiProof::from_bytes(alleged_solution, self.int_size_bits)?;
+        self.verify_proof(challenge, difficulty, &proof)
+    }
+}
+#[cfg(feature = "std")]
+impl<H: VDFHash> WesolowskiVDF<H> {
+    /// Switches to the hash function `G` for creating the discriminant and the
+    /// proof prime.  Proofs made with one hash function do not verify with
+    /// another.
+    pub fn with_hash<G: VDFHash>(self) -> WesolowskiVDF<G> {
+        WesolowskiVDF {
+            int_size_bits: self.int_size_bits,
+            memory_bytes: self.memory_bytes,
+            hash: PhantomData,
+        }
+    }
+
+    /// Same as `VDF::verify`, for a proof that has already been parsed.  Fails
+    /// if the proof is for a different discriminant size.
+    pub fn verify_proof(
+        &self,
+        challenge: &[u8],
+        difficulty: u64,
+        proof: &WesolowskiProof,
+    ) -> Result<(), super::InvalidProof> {
+        if proof.int_size_bits != self.int_size_bits {
+            return Err(super::InvalidProof);
+        }
+        proof.verify::<GmpClassGroup, H>(challenge, difficulty)
+    }
+
+    /// Verifies several solutions for the same `challenge`, each with its own
+    /// difficulty.  The discriminant and the base element are only computed
+    /// once; the results are the same as calling `VDF::verify` on each pair.
+    pub fn verify_batch(
+        &self,
+        challenge: &[u8],
+        solutions: &[(u64, &[u8])],
+    ) -> Vec<Result<(), super::InvalidProof>> {
+        if check_int_size_bits(self.int_size_bits).is_err() {
+            return vec![Err(super::InvalidProof); solutions.len()];
+        }
+        let discriminant: <GmpClassGroup as ClassGroup>::BigNum =
+            super::create_discriminant::create_discriminant_with_hash::<_, H>(challenge, self.int_size_bits);
+        let x = GmpClassGroup::from_ab_discriminant(2.into(), 1.into(), discriminant.clone());
+        solutions
+            .iter()
+            .map(|&(difficulty, solution)| {
+                let proof = WesolowskiProof::from_bytes(solution, self.int_size_bits)?;
+                check_wesolowski_proof_from::<_, _, H>(&x, &discriminant, &proof, difficulty)
+                    .map_err(|()| super::InvalidProof)
+            })
+            .collect()
+    }
+
+    /// Performs the first `iterations` squarings of the chain for `challenge`,
+    /// so that the computation can be persisted and resumed later with
+    /// `WesolowskiVDF::calculate_y_from`.
+    pub fn checkpoint(&self, challenge: &[u8], iterations: u64) -> Checkpoint {
+        let discriminant = super::create_discriminant::create_discriminant_with_hash::<_, H>(challenge, self.int_size_bits);
+        let mut x = GmpClassGroup::from_ab_discriminant(2.into(), 1.into(), discriminant);
+        x.repeated_square(iterations);
+        Checkpoint {
+            iterations,
+            element: serialize_element(&x, self.int_size_bits.into()),
+        }
+    }
+
+    /// Resumes the chain for `challenge` from `element`, the serialized group
+    /// element reached after `done_iterations` squarings, and returns the same
+    /// output as `VDF::calculate_y(challenge, total)`.
+    pub fn calculate_y_from(
+        &self,
+        challenge: &[u8],
+        element: &[u8],
+        done_iterations: u64,
+        total: u64,
+    ) -> Result<Vec<u8>, Bad> {
+        check_int_size_bits(self.int_size_bits)?;
+        super::VDF::check_difficulty(self, total)?;
+        if done_iterations > total {
+            return Err(Bad(format!(
+                "Cannot resume after {} iterations when only {} are needed",
+                done_iterations, total
+            )));
+        }
+        if element.len() != 2 * ((usize::from(self.int_size_bits) + 16) >> 4) {
+            return Err(Bad("Checkpoint element does not match the discriminant size".to_owned()));
+        }
+        let discriminant = super::create_discriminant::create_discriminant_with_hash::<_, H>(challenge, self.int_size_bits);
+        let mut y = GmpClassGroup::try_from_bytes(element, discriminant)
+            .ok_or_else(|| Bad("Checkpoint element is not in the class group of the challenge".to_owned()))?;
+        y.repeated_square(total - done_iterations);
+        Ok(serialize_element(&y, self.int_size_bits.into()))
     }
 }
+
+#[cfg(feature = "std")]
+/// Memory budget used by `approximate_parameters`, in bytes.
+pub const DEFAULT_MEMORY_BYTES: u64 = 10_000_000;
+
+#[cfg(feature = "std")]
 /// To quote the original Python code:
 ///
 /// > Create `L` and `k` parameters from papers, based on how many iterations
 /// > need to be performed, and how much memory should be used.
 pub fn approximate_parameters(t: f64) -> (usize, u8, u64) {
-    let log_memory = (10_000_000.0f64).log2();
+    approximate_parameters_with_memory(t, DEFAULT_MEMORY_BYTES)
+}
+
+#[cfg(feature = "std")]
+/// Same as `approximate_parameters`, for a memory budget of `memory_bytes`
+/// instead of the default 10 MB.
+pub fn approximate_parameters_with_memory(t: f64, memory_bytes: u64) -> (usize, u8, u64) {
+    let log_memory = (memory_bytes as f64).log2();
     let log_t = (t as f64).log2();
     let l = if log_t - log_memory > 0. {
         2.0f64.powf(log_memory - 20.).ceil()
@@ -104,36 +312,30 @@ pub fn approximate_parameters(t: f64) -> (usize, u8, u64) {
 }
 
 fn u64_to_bytes(q: u64) -> [u8; 8] {
-    if false {
-        // This use of `std::mem::transumte` is correct, but still not justified.
-        unsafe { std::mem::transmute(q.to_be()) }
-    } else {
-        [
-            (q >> 56) as u8,
-            (q >> 48) as u8,
-            (q >> 40) as u8,
-            (q >> 32) as u8,
-            (q >> 24) as u8,
-            (q >> 16) as u8,
-            (q >> 8) as u8,
-            q as u8,
-        ]
-    }
+    q.to_be_bytes()
 }
 
 /// Quote:
 ///
 /// > Creates a random prime based on input s.
-fn hash_prime<T: BigNum>(seed: &[&[u8]]) -> T {
+///
+/// For `j = 0, 1, 2, ...`, the candidate is the first 16 bytes, read as a
+/// big-endian unsigned integer, of
+///
+/// ```text
+/// H(b"prime" || j as 8 big-endian bytes || seed[0] || seed[1] || ...)
+/// ```
+///
+/// and the first candidate that passes `BigNum::probab_prime(2)` is returned.
+/// The parts of `seed` are concatenated without any length prefix.  The result
+/// only depends on `seed` and `H`.
+pub fn hash_prime<T: BigNum, H: VDFHash>(seed: &[&[u8]]) -> T {
     let mut j = 0u64;
     loop {
-        let mut hasher = Sha256::new();
-        hasher.input(b"prime");
-        hasher.input(u64_to_bytes(j));
-        for i in seed {
-            hasher.input(i);
-        }
-        let n = T::from(&hasher.fixed_result()[..16]);
+        let j_bytes = u64_to_bytes(j);
+        let mut parts: Vec<&[u8]> = vec![b"prime", &j_bytes];
+        parts.extend_from_slice(seed);
+        let n = T::from(&H::digest(&parts)[..16]);
         if n.probab_prime(2) {
             break n;
         }
@@ -141,6 +343,7 @@ fn hash_prime<T: BigNum>(seed: &[&[u8]]) -> T {
     }
 }
 
+#[cfg(feature = "std")]
 /// Quote:
 ///
 /// > Get“s the ith block of `2^T // B`, such that `sum(get_block(i) * 2^(k*i))
@@ -153,7 +356,21 @@ fn get_block<T: BigNumExt>(i: u64, k: u8, t: u64, b: &T) -> T {
     res / b
 }
 
-fn eval_optimized<T, U: BigNumExt, L: ClassGroup<BigNum = U> + Eq + Hash>(
+/// `Send + Sync` with the `rayon` feature, which proves on several threads,
+/// and no bound at all without it.
+#[cfg(all(feature = "std", feature = "rayon"))]
+pub trait ThreadSafe: Send + Sync {}
+#[cfg(all(feature = "std", feature = "rayon"))]
+impl<T: Send + Sync> ThreadSafe for T {}
+/// `Send + Sync` with the `rayon` feature, which proves on several threads,
+/// and no bound at all without it.
+#[cfg(all(feature = "std", not(feature = "rayon")))]
+pub trait ThreadSafe {}
+#[cfg(all(feature = "std", not(feature = "rayon")))]
+impl<T> ThreadSafe for T {}
+
+#[cfg(feature = "std")]
+fn eval_optimized<T, U: BigNumExt + ThreadSafe, L: ClassGroup<BigNum = U> + Eq + Hash + ThreadSafe>(
     h: &L,
     b: &U,
     t: usize,
@@ -162,7 +379,7 @@ fn eval_optimized<T, U: BigNumExt, L: ClassGroup<BigNum = U> + Eq + Hash>(
     powers: &T,
 ) -> L
 where
-    T: for<'a> std::ops::Index<&'a u64, Output = L>,
+    T: for<'a> core::ops::Index<&'a u64, Output = L> + ThreadSafe,
 {
     assert!(k > 0, "k cannot be zero");
     assert!(l > 0, "l cannot be zero");
@@ -176,51 +393,94 @@ where
         k < (mem::size_of::<usize>() << 3) as u8,
         "k must be less than the number of bits in a usize"
     );
+    let end_of_loop = ((t as f64) / kl as f64).ceil() as usize;
+    assert!(end_of_loop == 0 || (end_of_loop as u64 - 1).checked_mul(l as u64).is_some());
+
+    // The contribution of each of the `l` segments only depends on `j`, so the
+    // segments can be computed independently and combined in order afterwards
+    #[cfg(feature = "rayon")]
+    let segments = (0..l)
+        .into_par_iter()
+        .map(|j| eval_segment(h, b, t, k, l, j, powers))
+        .collect::<Vec<_>>();
+    #[cfg(not(feature = "rayon"))]
+    let segments = (0..l)
+        .map(|j| eval_segment(h, b, t, k, l, j, powers))
+        .collect::<Vec<_>>();
+
+    combine_segments(h, k, segments)
+}
+
+/// The contribution of segment `j` to the proof, as computed by
+/// `eval_optimized`.
+#[cfg(feature = "std")]
+fn eval_segment<T, U: BigNumExt, L: ClassGroup<BigNum = U> + Eq + Hash>(
+    h: &L,
+    b: &U,
+    t: usize,
+    k: u8,
+    l: usize,
+    j: usize,
+    powers: &T,
+) -> L
+where
+    T: for<'a> core::ops::Index<&'a u64, Output = L>,
+{
+    let kl = k as usize * l;
     let k1 = k >> 1;
     let k0 = k - k1;
-    let mut x = h.identity();
     let identity = h.identity();
-    let k_exp = 1usize << k;
     let k0_exp = 1usize << k0;
     let k1_exp = 1usize << k1;
-    for j in (0..l).rev() {
-        x.pow(U::from(k_exp as u64));
-        let mut ys: HashMap<U, L> = HashMap::new();
-        for b in 0..1usize << k {
-            ys.entry(U::from(b as u64))
-                .or_insert_with(|| identity.clone());
+    let end_of_loop = ((t as f64) / kl as f64).ceil() as usize;
+
+    let mut ys: HashMap<U, L> = HashMap::new();
+    for b in 0..1usize << k {
+        ys.entry(U::from(b as u64))
+            .or_insert_with(|| identity.clone());
+    }
+    for i in 0..end_of_loop {
+        if t < k as usize * (i * l + j + 1) {
+            continue;
         }
-        let end_of_loop = ((t as f64) / kl as f64).ceil() as usize;
-        assert!(end_of_loop == 0 || (end_of_loop as u64 - 1).checked_mul(l as u64).is_some());
-        for i in 0..end_of_loop {
-            if t < k as usize * (i * l + j + 1) {
-                continue;
-            }
-            let b = get_block((i as u64) * (l as u64), k, t as _, b);
-            *ys.get_mut(&b).unwrap() *= &powers[&((i * kl) as _)];
+        let b = get_block((i * l + j) as u64, k, t as _, b);
+        *ys.get_mut(&b).unwrap() *= &powers[&((i * kl) as _)];
+    }
+
+    let mut segment = identity.clone();
+    for b1 in 0..k1_exp {
+        let mut z = identity.clone();
+        for b0 in 0..k0_exp {
+            z *= &ys[&U::from((b1 * k0_exp + b0) as u64)]
         }
+        z.pow(U::from((b1 as u64) * (k0_exp as u64)));
+        segment *= &z;
+    }
 
+    for b0 in 0..k0_exp {
+        let mut z = identity.clone();
         for b1 in 0..k1_exp {
-            let mut z = identity.clone();
-            for b0 in 0..k0_exp {
-                z *= &ys[&U::from((b1 * k0_exp + b0) as u64)]
-            }
-            z.pow(U::from((b1 as u64) * (k0_exp as u64)));
-            x *= &z;
+            z *= &ys[&U::from((b1 * k0_exp + b0) as u64)];
         }
+        z.pow(U::from(b0 as u64));
+        segment *= &z;
+    }
+    segment
+}
 
-        for b0 in 0..k0_exp {
-            let mut z = identity.clone();
-            for b1 in 0..k1_exp {
-                z *= &ys[&U::from((b1 * k0_exp + b0) as u64)];
-            }
-            z.pow(U::from(b0 as u64));
-            x *= &z;
-        }
+/// Combines the contributions of segments `0..l`, in that order, into the
+/// proof.
+#[cfg(feature = "std")]
+fn combine_segments<U: BigNumExt, L: ClassGroup<BigNum = U>>(h: &L, k: u8, segments: Vec<L>) -> L {
+    let mut x = h.identity();
+    for segment in segments.into_iter().rev() {
+        x.pow(U::from((1usize << k) as u64));
+        x *= &segment;
     }
     x
 }
 
+#[cfg(feature = "std")]
 pub fn generate_output<U, T: BigNumExt, V: ClassGroup<BigNum = T> + Eq + Hash>(
     x: &V,
     iterations: u64,
@@ -228,7 +488,7 @@ pub fn generate_output<U, T: BigNumExt, V: ClassGroup<BigNum = T> + Eq + Hash>(
     int_size_bits: usize,
 ) -> Vec<u8>
 where
-    U: for<'a> std::ops::Index<&'a u64, Output = V>,
+    U: for<'a> core::ops::Index<&'a u64, Output = V>,
 {
     let element_len = 2 * ((int_size_bits + 16) >> 4);
 
@@ -245,7 +505,13 @@ where
     y_buf
 }
 
-pub fn generate_proof<U, T: BigNumExt, V: ClassGroup<BigNum = T> + Eq + Hash>(
+#[cfg(feature = "std")]
+pub fn generate_proof<
+    U,
+    T: BigNumExt + ThreadSafe,
+    V: ClassGroup<BigNum = T> + Eq + Hash + ThreadSafe,
+    H: VDFHash,
+>(
     x: &V,
     iterations: u64,
     k: u8,
@@ -254,41 +520,42 @@ pub fn generate_proof<U, T: BigNumExt, V: ClassGroup<BigNum = T> + Eq + Hash>(
     int_size_bits: usize,
 ) -> V
 where
-    U: for<'a> std::ops::Index<&'a u64, Output = V>,
+    U: for<'a> core::ops::Index<&'a u64, Output = V> + ThreadSafe,
 {
+    #[cfg(feature = "logging")]
+    log::debug!("y after {} iterations: {:?}", iterations, &powers[&iterations]);
+
+    let b = fiat_shamir_challenge::<_, _, H>(x, &powers[&iterations], int_size_bits);
+    eval_optimized(x, &b, iterations as _, k, l, powers)
+}
+
+/// The Fiat–Shamir challenge of a Wesolowski proof: the prime `hash_prime`
+/// derives from `x` and `y`, each serialized with `ClassGroup::serialize` into
+/// `2 * ((int_size_bits + 16) >> 4)` bytes.
+pub fn fiat_shamir_challenge<T: BigNum, V: ClassGroup<BigNum = T>, H: VDFHash>(
+    x: &V,
+    y: &V,
+    int_size_bits: usize,
+) -> T {
     let element_len = 2 * ((int_size_bits + 16) >> 4);
     let mut x_buf = vec![0; element_len];
     x.serialize(&mut x_buf[..])
         .expect(super::INCORRECT_BUFFER_SIZE);
-
     let mut y_buf = vec![0; element_len];
-
-    powers[&iterations]
-        .serialize(&mut y_buf[..])
+    y.serialize(&mut y_buf[..])
         .expect(super::INCORRECT_BUFFER_SIZE);
-    println!("powers[&iterations]: {:?}", &powers[&iterations]);
-    println!("y_buf[..]: {:?}", &y_buf[..]);
-
-    let b = hash_prime(&[&x_buf[..], &y_buf[..]]);
-    eval_optimized(&x, &b, iterations as _, k, l, powers)
+    hash_prime::<_, H>(&[&x_buf[..], &y_buf[..]])
 }
 
 /// Verify a proof, according to the Wesolowski paper.
-pub fn verify_proof<T: BigNum, V: ClassGroup<BigNum = T>>(
+pub fn verify_proof<T: BigNum, V: ClassGroup<BigNum = T>, H: VDFHash>(
     mut x: V,
     y: &V,
     mut proof: V,
     t: u64,
     int_size_bits: usize,
 ) -> Result<(), ()> {
-    let element_len = 2 * ((int_size_bits + 16) >> 4);
-    let mut x_buf = vec![0; element_len];
-    x.serialize(&mut x_buf[..])
-        .expect(super::INCORRECT_BUFFER_SIZE);
-    let mut y_buf = vec![0; element_len];
-    y.serialize(&mut y_buf[..])
-        .expect(super::INCORRECT_BUFFER_SIZE);
-    let b = hash_prime(&[&x_buf[..], &y_buf[..]]);
+    let b = fiat_shamir_challenge::<_, _, H>(&x, y, int_size_bits);
     let mut r = T::from(0);
     r.mod_powm(&T::from(2u64), &T::from(t), &b);
     proof.pow(b);
@@ -301,89 +568,396 @@ pub fn verify_proof<T: BigNum, V: ClassGroup<BigNum = T>>(
     }
 }
 
-pub fn generate_y<T: BigNumExt, V: ClassGroup<BigNum = T> + Eq + Hash>(
+/// Chooses the segment parameters `l` and `k` for `iterations` squarings and
+/// a memory budget of `memory_bytes`, along with `q = l * k`, the distance
+/// between the stored powers.
+#[cfg(feature = "std")]
+fn segment_parameters(iterations: usize, memory_bytes: u64) -> Result<(usize, u8, usize), Bad> {
+    if (iterations as u128) >= (1u128 << 53) {
+        return Err(Bad("The number of iterations must be less than 2^53".to_owned()));
+    }
+    let (l, k, _) = approximate_parameters_with_memory(iterations as f64, memory_bytes);
+    match l.checked_mul(k as _) {
+        Some(q) if q > 0 => Ok((l, k, q)),
+        _ => Err(Bad(format!(
+            "Cannot split {} iterations into segments with a memory budget of {} bytes",
+            iterations, memory_bytes
+        ))),
+    }
+}
+
+#[cfg(feature = "std")]
+pub fn generate_y<T: BigNumExt, V: ClassGroup<BigNum = T> + Eq + Hash, H: VDFHash>(
     challenge: &[u8],
     iterations: usize,
     int_size_bits: u16,
-) -> Vec<u8>
+    memory_bytes: u64,
+) -> Result<Vec<u8>, Bad>
 where
-    for<'a, 'b> &'a V: std::ops::Mul<&'b V, Output = V>,
-    for<'a, 'b> &'a V::BigNum: std::ops::Mul<&'b V::BigNum, Output = V::BigNum>,
+    for<'a, 'b> &'a V: core::ops::Mul<&'b V, Output = V>,
+    for<'a, 'b> &'a V::BigNum: core::ops::Mul<&'b V::BigNum, Output = V::BigNum>,
 {
-    let discriminant = super::create_discriminant::create_discriminant(&challenge, int_size_bits);
+    check_int_size_bits(int_size_bits)?;
+    let (_, _, q) = segment_parameters(iterations, memory_bytes)?;
 
+    let discriminant = super::create_discriminant::create_discriminant_with_hash::<_, H>(challenge, int_size_bits);
     let x = V::from_ab_discriminant(2.into(), 1.into(), discriminant);
-    assert!((iterations as u128) < (1u128 << 53));
-
-    let (l, k, _) = approximate_parameters(iterations as f64);
-
-    let q = l.checked_mul(k as _).expect("bug");
 
     let powers = iterate_squarings(
         x.clone(),
-        (0..=iterations / q + 1)
+        (0..=iterations / q)
             .map(|i| i * q)
             .chain(Some(iterations))
             .map(|x| x as _),
     );
 
-    return generate_output(&x, iterations as _, &powers, int_size_bits.into());
+    Ok(generate_output(&x, iterations as _, &powers, int_size_bits.into()))
 }
 
-pub fn create_proof_of_time_wesolowski<T: BigNumExt, V: ClassGroup<BigNum = T> + Eq + Hash>(
+#[cfg(feature = "std")]
+pub fn create_proof_of_time_wesolowski<
+    T: BigNumExt + ThreadSafe,
+    V: ClassGroup<BigNum = T> + Eq + Hash + ThreadSafe,
+    H: VDFHash,
+>(
     challenge: &[u8],
     iterations: usize,
     int_size_bits: u16,
-) -> Vec<u8>
+    memory_bytes: u64,
+) -> Result<Vec<u8>, Bad>
 where
-    for<'a, 'b> &'a V: std::ops::Mul<&'b V, Output = V>,
-    for<'a, 'b> &'a V::BigNum: std::ops::Mul<&'b V::BigNum, Output = V::BigNum>,
+    for<'a, 'b> &'a V: core::ops::Mul<&'b V, Output = V>,
+    for<'a, 'b> &'a V::BigNum: core::ops::Mul<&'b V::BigNum, Output = V::BigNum>,
 {
-    let discriminant = super::create_discriminant::create_discriminant(&challenge, int_size_bits);
+    check_int_size_bits(int_size_bits)?;
+    let (l, k, q) = segment_parameters(iterations, memory_bytes)?;
 
+    let discriminant = super::create_discriminant::create_discriminant_with_hash::<_, H>(challenge, int_size_bits);
     let x = V::from_ab_discriminant(2.into(), 1.into(), discriminant);
-    assert!((iterations as u128) < (1u128 << 53));
-
-    let (l, k, _) = approximate_parameters(iterations as f64);
-
-    let q = l.checked_mul(k as _).expect("bug");
 
+    // The proof can't be computed alongside the squaring chain: its
+    // Fiat–Shamir challenge is derived from `y`, the very last power, so
+    // proving has to wait for the whole chain.  Only the segments of the
+    // proof itself are computed in parallel, by `eval_optimized`.
     let powers = iterate_squarings(
         x.clone(),
-        (0..=iterations / q + 1)
+        (0..=iterations / q)
             .map(|i| i * q)
             .chain(Some(iterations))
             .map(|x| x as _),
     );
+    let proof = generate_proof::<_, _, _, H>(&x, iterations as _, k, l, &powers, int_size_bits.into());
 
-    let proof = generate_proof(&x, iterations as _, k, l, &powers, int_size_bits.into());
-    serialize(&[proof], &powers[&(iterations as _)], int_size_bits.into())
+    Ok(serialize(&[proof], &powers[&(iterations as _)], int_size_bits.into()))
 }
 
-pub fn check_proof_of_time_wesolowski<T: BigNum, V: ClassGroup<BigNum = T>>(
+pub fn check_wesolowski_proof<T: BigNumExt, V: ClassGroup<BigNum = T>, H: VDFHash>(
     challenge: &[u8],
-    proof_blob: &[u8],
+    proof: &WesolowskiProof,
     iterations: u64,
-    int_size_bits: u16,
-) -> Result<(), ()>
-where
-    T: BigNumExt,
-{
-    let discriminant: T = super::create_discriminant::create_discriminant(challenge, int_size_bits);
-    let x = V::from_ab_discriminant(2.into(), 1.into(), discriminant.clone());
-    if (usize::MAX - 16) < int_size_bits.into() {
+) -> Result<(), ()> {
+    if check_int_size_bits(proof.int_size_bits).is_err() {
         return Err(());
     }
-    let int_size = (usize::from(int_size_bits) + 16) >> 4;
-    if int_size * 4 != proof_blob.len() {
+    let discriminant: T = super::create_discriminant::create_discriminant_with_hash::<_, H>(challenge, proof.int_size_bits);
+    let x = V::from_ab_discriminant(2.into(), 1.into(), discriminant.clone());
+    check_wesolowski_proof_from::<_, _, H>(&x, &discriminant, proof, iterations)
+}
+
+/// Same as `check_wesolowski_proof`, for a base element `x` and its
+/// `discriminant` that have already been computed from the challenge.
+fn check_wesolowski_proof_from<T: BigNum, V: ClassGroup<BigNum = T>, H: VDFHash>(
+    x: &V,
+    discriminant: &T,
+    proof: &WesolowskiProof,
+    iterations: u64,
+) -> Result<(), ()> {
+    if !proof.has_valid_lengths() {
         return Err(());
     }
+    let pi = V::try_from_bytes(&proof.proof, discriminant.clone()).ok_or(())?;
+    let y = V::try_from_bytes(&proof.y, discriminant.clone()).ok_or(())?;
 
-    let (result_bytes, proof_bytes) = proof_blob.split_at(2 * int_size);
-    let proof = ClassGroup::from_bytes(proof_bytes, discriminant.clone());
-    let y = ClassGroup::from_bytes(result_bytes, discriminant);
+    #[cfg(feature = "logging")]
+    log::debug!("verifying y: {:?}", y);
+
+    verify_proof::<_, _, H>(x.clone(), &y, pi, iterations, proof.int_size_bits.into())
+}
 
-    println!("{:?}", y);
+#[cfg(all(test, feature = "std"))]
+mod test {
+    use super::*;
+    use crate::{VDFParams, VDF};
 
-    verify_proof(x, &y, proof, iterations, int_size_bits.into())
+    #[test]
+    fn resume_from_checkpoint() {
+        let vdf = WesolowskiVDFParams(512).new();
+        let straight = vdf.calculate_y(b"\xaa", 100).unwrap();
+
+        let checkpoint = Checkpoint::from_bytes(&vdf.checkpoint(b"\xaa", 50).to_bytes()).unwrap();
+        assert_eq!(checkpoint.iterations, 50);
+
+        let resumed = vdf
+            .calculate_y_from(b"\xaa", &checkpoint.element, checkpoint.iterations, 100)
+            .unwrap();
+        assert_eq!(resumed, straight);
+
+        assert!(vdf.calculate_y_from(b"\xaa", &checkpoint.element, 101, 100).is_err());
+        assert!(vdf.calculate_y_from(b"\xaa", &checkpoint.element[1..], 50, 100).is_err());
+        assert!(vdf.calculate_y_from(b"\xaa", &checkpoint.element, 0, 0).is_err());
+        assert!(vdf.calculate_y_from(b"\xaa", &checkpoint.element, 50, 1 << 53).is_err());
+    }
+
+    #[test]
+    fn resume_from_corrupted_checkpoint() {
+        let vdf = WesolowskiVDFParams(512).new();
+        let checkpoint = vdf.checkpoint(b"\xaa", 50);
+
+        let mut corrupted = checkpoint.element.clone();
+        *corrupted.last_mut().unwrap() ^= 1;
+        assert!(vdf.calculate_y_from(b"\xaa", &corrupted, 50, 100).is_err());
+
+        // A checkpoint of another challenge is not in the same class group
+        assert!(vdf.calculate_y_from(b"\xbb", &checkpoint.element, 50, 100).is_err());
+    }
+    #[test]
+    fn difficulty_bounds() {
+        let vdf = WesolowskiVDFParams(512).new();
+
+        assert!(vdf.check_difficulty(0).is_err());
+        assert!(vdf.check_difficulty(1 << 53).is_err());
+        assert!(vdf.check_difficulty(u64::MAX).is_err());
+        assert!(vdf.check_difficulty(1 << 20).is_ok());
+        assert!(vdf.check_difficulty((1 << 53) - 1).is_ok());
+
+        assert!(vdf.solve(b"\xaa", 0).is_err());
+        assert!(vdf.calculate_y(b"\xaa", 1 << 53).is_err());
+    }
+
+    #[test]
+    fn invalid_parameters() {
+        let blob = WesolowskiVDFParams(512).new().solve(b"\xaa", 100).unwrap();
+
+        let no_bits = WesolowskiVDFParams(0).new();
+        assert!(no_bits.solve(b"\xaa", 100).is_err());
+        assert!(no_bits.calculate_y(b"\xaa", 100).is_err());
+        assert!(no_bits.calculate_y_from(b"\xaa", &blob[..2], 0, 100).is_err());
+        assert!(no_bits.verify(b"\xaa", 100, &[0; 4]).is_err());
+
+        for &int_size_bits in &[1, 2, 3, 8, 17] {
+            let too_small = WesolowskiVDFParams(int_size_bits).new();
+            assert!(too_small.solve(b"\xaa", 100).is_err());
+            assert!(too_small.calculate_y(b"\xaa", 100).is_err());
+        }
+        assert!(WesolowskiVDFParams(18).new().solve(b"\xaa", 100).is_ok());
+
+        let no_memory = WesolowskiVDFParams(512).new_with_memory(0);
+        assert!(no_memory.solve(b"\xaa", 100).is_err());
+        assert!(no_memory.calculate_y(b"\xaa", 100).is_err());
+
+        type Big = <GmpClassGroup as ClassGroup>::BigNum;
+        let too_many = 1 << 53;
+        assert!(
+            generate_y::<Big, GmpClassGroup, Sha256Hash>(b"\xaa", too_many, 512, DEFAULT_MEMORY_BYTES)
+                .is_err()
+        );
+        assert!(create_proof_of_time_wesolowski::<Big, GmpClassGroup, Sha256Hash>(
+            b"\xaa",
+            too_many,
+            512,
+            DEFAULT_MEMORY_BYTES
+        )
+        .is_err());
+    }
+    #[test]
+    fn proof_round_trip() {
+        let vdf = WesolowskiVDFParams(512).new();
+        let blob = vdf.solve(b"\xaa", 100).unwrap();
+
+        let proof = WesolowskiProof::from_bytes(&blob, 512).unwrap();
+        assert_eq!(proof.y.len(), WesolowskiProof::element_len(512));
+        assert_eq!(proof.to_bytes(), blob);
+        assert!(vdf.verify_proof(b"\xaa", 100, &proof).is_ok());
+        assert!(vdf.verify_proof(b"\xaa", 102, &proof).is_err());
+    }
+
+    #[test]
+    fn batch_verification() {
+        let vdf = WesolowskiVDFParams(512).new();
+        let short = vdf.solve(b"\xaa", 100).unwrap();
+        let long = vdf.solve(b"\xaa", 200).unwrap();
+
+        let solutions: Vec<(u64, &[u8])> = vec![
+            (100, &short),
+            (200, &short),
+            (200, &long),
+            (102, &long),
+            (100, &short[1..]),
+            (100, &short),
+        ];
+        let results = vdf.verify_batch(b"\xaa", &solutions);
+        assert_eq!(results.len(), solutions.len());
+        for (result, &(difficulty, solution)) in results.iter().zip(&solutions) {
+            assert_eq!(*result, vdf.verify(b"\xaa", difficulty, solution));
+        }
+        assert_eq!(
+            results.iter().map(Result::is_ok).collect::<Vec<_>>(),
+            [true, false, true, false, false, true]
+        );
+        assert!(vdf.verify_batch(b"\xaa", &[]).is_empty());
+    }
+
+    #[test]
+    fn generic_verify() {
+        // `WesolowskiProof::verify` is what a `no_std` build exposes
+        let blob = WesolowskiVDFParams(512).new().solve(b"\xaa", 100).unwrap();
+        let proof = WesolowskiProof::from_bytes(&blob, 512).unwrap();
+
+        assert!(proof.verify::<GmpClassGroup, Sha256Hash>(b"\xaa", 100).is_ok());
+        assert!(proof.verify::<GmpClassGroup, Sha256Hash>(b"\xaa", 102).is_err());
+    }
+
+    #[test]
+    fn malformed_proof_lengths() {
+        let vdf = WesolowskiVDFParams(512).new();
+        let blob = vdf.solve(b"\xaa", 100).unwrap();
+
+        assert!(WesolowskiProof::from_bytes(&blob[1..], 512).is_err());
+        assert!(WesolowskiProof::from_bytes(&blob, 1024).is_err());
+        assert!(vdf.verify(b"\xaa", 100, &blob[..blob.len() - 2]).is_err());
+
+        let mut proof = WesolowskiProof::from_bytes(&blob, 512).unwrap();
+        proof.proof.pop();
+        assert!(vdf.verify_proof(b"\xaa", 100, &proof).is_err());
+        proof.int_size_bits = 1024;
+        assert!(vdf.verify_proof(b"\xaa", 100, &proof).is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn proof_serde_round_trip() {
+        let blob = WesolowskiVDFParams(512).new().solve(b"\xaa", 100).unwrap();
+        let proof = WesolowskiProof::from_bytes(&blob, 512).unwrap();
+
+        let json = serde_json::to_string(&proof).unwrap();
+        assert_eq!(serde_json::from_str::<WesolowskiProof>(&json).unwrap(), proof);
+    }
+    #[test]
+    fn memory_budget() {
+        let t = (1u64 << 40) as f64;
+        let (small_l, _, _) = approximate_parameters_with_memory(t, 1_000_000);
+        let (default_l, _, _) = approximate_parameters(t);
+        let (large_l, _, _) = approximate_parameters_with_memory(t, 100_000_000);
+
+        assert_eq!(approximate_parameters_with_memory(t, DEFAULT_MEMORY_BYTES).0, default_l);
+        assert!(small_l < default_l);
+        assert!(default_l < large_l);
+
+        let proof = WesolowskiVDFParams(512).new().solve(b"\xaa", 1000).unwrap();
+        let small = WesolowskiVDFParams(512).new_with_memory(1 << 10);
+        assert_eq!(small.solve(b"\xaa", 1000).unwrap(), proof);
+    }
+    fn proof_with_segments(k: u8, l: usize, t: usize) -> Vec<u8> {
+        let discriminant = super::super::create_discriminant::create_discriminant(b"\xaa", 512);
+        let x = GmpClassGroup::from_ab_discriminant(2.into(), 1.into(), discriminant);
+        let q = k as usize * l;
+        let powers = iterate_squarings(
+            x.clone(),
+            (0..=t / q).map(|i| i * q).chain(Some(t)).map(|x| x as _),
+        );
+        let proof = generate_proof::<_, _, _, Sha256Hash>(&x, t as _, k, l, &powers, 512);
+        serialize(&[proof], &powers[&(t as u64)], 512)
+    }
+
+    #[test]
+    fn segments_combine_in_order() {
+        let t = 2000;
+        let proof = WesolowskiVDFParams(512).new().solve(b"\xaa", t as _).unwrap();
+
+        assert_eq!(proof_with_segments(4, 1, t), proof);
+        assert_eq!(proof_with_segments(4, 5, t), proof);
+        assert_eq!(proof_with_segments(3, 7, t), proof);
+    }
+
+    #[test]
+    #[cfg(feature = "rayon")]
+    fn parallel_segments_match_sequential() {
+        let (t, k, l) = (20_000u64, 4, 5);
+        let discriminant = super::super::create_discriminant::create_discriminant(b"\xaa", 512);
+        let x = GmpClassGroup::from_ab_discriminant(2.into(), 1.into(), discriminant);
+        let q = k as u64 * l as u64;
+        let powers = iterate_squarings(x.clone(), (0..=t / q).map(|i| i * q).chain(Some(t)));
+        let b = fiat_shamir_challenge::<_, _, Sha256Hash>(&x, &powers[&t], 512);
+
+        // Proves one segment after the other on this thread
+        let segments = (0..l)
+            .map(|j| eval_segment(&x, &b, t as _, k, l, j, &powers))
+            .collect();
+        let sequential = combine_segments(&x, k, segments);
+
+        assert_eq!(eval_optimized(&x, &b, t as _, k, l, &powers), sequential);
+    }
+
+    #[test]
+    fn segments_use_their_own_blocks() {
+        // Segment `j` of round `i` must read block `i * l + j`; reading block
+        // `i * l` in every segment only happens to work for `l = 1`
+        let t = 1000;
+        for &(k, l) in &[(2, 3), (3, 7), (5, 2)] {
+            let blob = proof_with_segments(k, l, t);
+            let proof = WesolowskiProof::from_bytes(&blob, 512).unwrap();
+            assert_eq!(proof.verify::<GmpClassGroup, Sha256Hash>(b"\xaa", t as _), Ok(()));
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "blake3")]
+    fn hash_function_must_match() {
+        use crate::Blake3Hash;
+        let sha = WesolowskiVDFParams(512).new();
+        let blake = WesolowskiVDFParams(512).new().with_hash::<Blake3Hash>();
+        let sha_proof = sha.solve(b"\xaa", 100).unwrap();
+        let blake_proof = blake.solve(b"\xaa", 100).unwrap();
+
+        assert_ne!(sha_proof, blake_proof);
+        assert!(blake.verify(b"\xaa", 100, &blake_proof).is_ok());
+        assert!(blake.verify(b"\xaa", 102, &blake_proof).is_err());
+        assert!(sha.verify(b"\xaa", 100, &blake_proof).is_err());
+        assert!(blake.verify(b"\xaa", 100, &sha_proof).is_err());
+        assert_eq!(
+            blake.calculate_y(b"\xaa", 100).unwrap(),
+            &blake_proof[..WesolowskiProof::element_len(512)]
+        );
+    }
+
+    #[test]
+    fn hash_prime_known_answers() {
+        type Big = <GmpClassGroup as ClassGroup>::BigNum;
+        let prime = |seed: &[&[u8]]| hash_prime::<Big, Sha256Hash>(seed).to_string();
+
+        assert_eq!(prime(&[]), "92759750565336558186611115603143196679");
+        assert_eq!(prime(&[b"\xaa"]), "126203949320643244010336637552384335929");
+        assert_eq!(prime(&[b"hello world"]), "305570005218113492240505782726944490371");
+        // The parts of the seed are concatenated
+        assert_eq!(prime(&[b"hello", b" ", b"world"]), prime(&[b"hello world"]));
+        assert_eq!(prime(&[b"\xaa"]), prime(&[b"\xaa"]));
+    }
+
+    #[test]
+    fn fiat_shamir_challenge_known_answer() {
+        type Big = <GmpClassGroup as ClassGroup>::BigNum;
+        let discriminant = super::super::create_discriminant::<Big>(b"\xaa", 512);
+        let x = GmpClassGroup::generator_for_discriminant(discriminant);
+        let mut y = x.clone();
+        y.repeated_square(100);
+
+        let challenge = fiat_shamir_challenge::<_, _, Sha256Hash>(&x, &y, 512);
+        assert_eq!(challenge.to_string(), "2079978803611524455886680947995066973");
+
+        let element_len = WesolowskiProof::element_len(512);
+        let mut xy = vec![0; 2 * element_len];
+        x.serialize(&mut xy[..element_len]).unwrap();
+        y.serialize(&mut xy[element_len..]).unwrap();
+        assert_eq!(challenge, hash_prime::<Big, Sha256Hash>(&[&xy]));
+    }
 }